@@ -53,6 +53,8 @@ def _load_native_module():
 _native = _load_native_module()
 
 look_up = _native.look_up
+look_up_entries = _native.look_up_entries
+look_up_text = _native.look_up_text
 get_sound = _native.get_sound
 gen_pitch_svg = _native.gen_pitch_svg
 WordType = _native.WordType
@@ -164,6 +166,16 @@ class Dictionary:
                 "This folder contains essential files needed for the add-on to work.\n\n"
             )
         return path
+
+    def look_up_vocabs(self, vocab_str: str, word_type: WordType) -> list:
+        try:
+            return look_up(self.get_assets_folder(), vocab_str, word_type)
+        except OSError as e:
+            showCritical("Accent Dict Add-on Error\n\n"
+                "The dictionary in the 'assets' folder can't be read.\n\n"
+                f"{e}\n\n"
+            )
+            return []
     
 
     def regenerated_headword_action(self):
@@ -171,7 +183,7 @@ class Dictionary:
         vocab_str = self.get_field("dict")
         vocabs = []
         if vocab_str is not None:
-            vocabs = look_up(self.get_assets_folder(), vocab_str, WordType.HEADWORD)
+            vocabs = self.look_up_vocabs(vocab_str, WordType.HEADWORD)
         for vocab in vocabs:
             vocab_menu = QMenu(vocab.head, self.editor.parentWindow)
 
@@ -190,7 +202,7 @@ class Dictionary:
         vocab_str = self.get_field("dict")
         vocabs = []
         if vocab_str is not None:
-            vocabs = look_up(self.get_assets_folder(), vocab_str, WordType.COMPOUND)
+            vocabs = self.look_up_vocabs(vocab_str, WordType.COMPOUND)
         for vocab in vocabs:
             vocab_menu = QMenu(vocab.head, self.editor.parentWindow)
 
@@ -209,7 +221,7 @@ class Dictionary:
         vocab_str = self.get_field("dict")
         vocabs = []
         if vocab_str is not None:
-            vocabs = look_up(self.get_assets_folder(), vocab_str, WordType.COUNTER)
+            vocabs = self.look_up_vocabs(vocab_str, WordType.COUNTER)
         for vocab in vocabs:
             vocab_menu = QMenu(vocab.head, self.editor.parentWindow)
 
@@ -1,10 +1,37 @@
 use core::{
+    fmt::{self, Debug, Formatter},
     mem::{align_of, size_of},
+    ops::Deref,
     slice,
 };
-use std::{fs::File, io::Read};
+use std::fs::File;
 
-use crate::Error;
+use memmap2::Mmap;
+
+use crate::{
+    Error,
+    storage::{Bytes, ReadAt},
+};
+
+/// How resource and index files are brought into memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Memory map the files. Falls back to `Read` for files that can't be mapped.
+    #[default]
+    Mmap,
+    /// Read the files into buffers.
+    Read,
+}
+
+/// Memory maps `file` if `mode` asks for it. `None` means the file has to be read instead.
+pub(crate) fn map_file(file: &File, mode: LoadMode) -> Option<Mmap> {
+    if mode != LoadMode::Mmap {
+        return None;
+    }
+    // SAFETY: dictionary files are never written by us. Modifying them while
+    // a dictionary is open is not supported, same as with the read path.
+    unsafe { Mmap::map(file) }.ok()
+}
 
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -81,8 +108,10 @@ pub(crate) unsafe trait TransmuteSafe: Default + Clone {
     }
 }
 
+/// The items from `base + start` to `base + end` of `file`, rounded up to whole items.
 pub(crate) fn read_vec<T: TransmuteSafe>(
-    file: &mut File,
+    file: &dyn ReadAt,
+    base: usize,
     start: usize,
     end: usize,
 ) -> Result<Option<Vec<T>>, Error> {
@@ -91,6 +120,117 @@ pub(crate) fn read_vec<T: TransmuteSafe>(
     }
     let size = (end - start).div_ceil(size_of::<T>());
     let mut buf = vec![T::default(); size];
-    file.read_exact(T::slice_as_bytes_mut(&mut buf))?;
+    file.read_exact_at(T::slice_as_bytes_mut(&mut buf), (base + start) as u64)?;
     Ok(Some(buf))
 }
+
+/// Like `read_vec`, but borrows the items from the file if it is in memory or mapped.
+pub(crate) fn load_region<T: TransmuteSafe>(
+    file: &dyn ReadAt,
+    base: usize,
+    start: usize,
+    end: usize,
+) -> Result<Option<Region<T>>, Error> {
+    let Some(bytes) = file.bytes() else {
+        return Ok(read_vec(file, base, start, end)?.map(Region::Owned));
+    };
+    if start == 0 || end == 0 {
+        return Ok(None);
+    }
+    let len = (end - start).div_ceil(size_of::<T>());
+    Region::from_map(&bytes, base + start, len).map(Some)
+}
+
+/// A slice of `T`, either read into a buffer or pointing into a file that is
+/// memory mapped or in memory already.
+#[derive(Clone)]
+pub(crate) enum Region<T> {
+    Owned(Vec<T>),
+    Mapped {
+        map: Bytes,
+        offset: usize,
+        len: usize,
+    },
+}
+
+impl<T: TransmuteSafe> Region<T> {
+    /// `len` items starting at byte `offset` of `map`.
+    /// The items are copied if they are not aligned for `T`.
+    pub(crate) fn from_map(map: &Bytes, offset: usize, len: usize) -> Result<Self, Error> {
+        let buf = (**map).as_ref().get(offset..).ok_or(Error::Transmute)?;
+        if T::slice_from_buf(buf, len).is_ok() {
+            return Ok(Region::Mapped {
+                map: map.clone(),
+                offset,
+                len,
+            });
+        }
+        let bytes = buf.get(..len * size_of::<T>()).ok_or(Error::Transmute)?;
+        let mut items = vec![T::default(); len];
+        T::slice_as_bytes_mut(&mut items).copy_from_slice(bytes);
+        Ok(Region::Owned(items))
+    }
+}
+
+impl<T: TransmuteSafe> Deref for Region<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Region::Owned(items) => items,
+            Region::Mapped { map, offset, len } => {
+                // USE INVARIANT: size and alignment are checked in `from_map`
+                T::slice_from_buf(&(**map).as_ref()[*offset..], *len)
+                    .expect("mapped region is validated on creation")
+                    .0
+            }
+        }
+    }
+}
+
+impl<T: Debug> Debug for Region<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Owned(items) => f.debug_tuple("Owned").field(items).finish(),
+            Region::Mapped { offset, len, .. } => f
+                .debug_struct("Mapped")
+                .field("offset", offset)
+                .field("len", len)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for Region<T> {
+    fn from(items: Vec<T>) -> Self {
+        Region::Owned(items)
+    }
+}
+
+#[test]
+fn test_region_from_map() {
+    use std::{io::Write, sync::Arc};
+
+    let path = std::env::temp_dir().join(format!("accent_dict_region_{}", std::process::id()));
+    let mut file = File::create(&path).unwrap();
+    file.write_all(&[0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0])
+        .unwrap();
+    let map: Bytes = Arc::new(map_file(&File::open(&path).unwrap(), LoadMode::Mmap).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    // unaligned, has to be copied
+    let region = Region::<LE32>::from_map(&map, 1, 3).unwrap();
+    assert!(matches!(region, Region::Owned(_)));
+    assert_eq!(
+        region.iter().map(|n| n.read()).collect::<Vec<_>>(),
+        [1, 2, 3]
+    );
+
+    let region = Region::<u8>::from_map(&map, 9, 4).unwrap();
+    assert!(matches!(region, Region::Mapped { .. }));
+    assert_eq!(*region, [3, 0, 0, 0]);
+
+    assert!(Region::<LE32>::from_map(&map, 1, 4).is_err());
+    assert!(Region::<u8>::from_map(&map, 14, 0).is_err());
+    assert!(map_file(&File::open("/dev/null").unwrap(), LoadMode::Read).is_none());
+}
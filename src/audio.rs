@@ -1,15 +1,23 @@
-use std::{fmt::Display, ops::Range, path::PathBuf};
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    ops::Range,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
 use crate::{
-    Error,
+    Error, LoadMode,
+    info::open_content,
     resource::{Nrsc, Rsc},
+    storage::{Storage, join},
 };
 
 const RSC_NAME: &str = "audio";
 
 pub struct Audio {
-    path: PathBuf,
-    res: Option<AudioResource>,
+    storage: Arc<dyn Storage>,
+    res: OnceLock<AudioResource>,
 }
 
 enum AudioResource {
@@ -19,43 +27,47 @@ enum AudioResource {
 
 impl Audio {
     pub fn new(path: &str) -> Result<Self, Error> {
+        Self::with_mode(path, LoadMode::default())
+    }
+
+    pub fn with_mode(path: &str, mode: LoadMode) -> Result<Self, Error> {
+        Self::with_storage(open_content(Path::new(path), mode)?)
+    }
+
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Result<Self, Error> {
         Ok(Audio {
-            path: PathBuf::from(path).join(RSC_NAME),
-            res: None,
+            storage,
+            res: OnceLock::new(),
         })
     }
 
-    pub fn init(&mut self) -> Result<(), Error> {
-        if self.res.is_none() {
-            self.path.push("index.nidx");
-            let nrsc_index_exists = self.path.exists();
-            self.path.pop();
-            self.res = Some(if nrsc_index_exists {
-                AudioResource::Nrsc(Nrsc::new(&self.path)?)
-            } else {
-                AudioResource::Rsc(Rsc::new(&self.path, RSC_NAME)?)
-            });
-        }
-        Ok(())
+    pub fn init(&self) -> Result<(), Error> {
+        self.res().map(|_| ())
     }
 
-    pub fn get(&mut self, id: &str) -> Result<&[u8], Error> {
-        self.init()?;
-        let Some(res) = self.res.as_mut() else {
-            unreachable!()
+    fn res(&self) -> Result<&AudioResource, Error> {
+        if let Some(res) = self.res.get() {
+            return Ok(res);
+        }
+        let storage = &*self.storage;
+        let res = if storage.exists(&join(RSC_NAME, "index.nidx")) {
+            AudioResource::Nrsc(Nrsc::new(storage, RSC_NAME)?)
+        } else {
+            AudioResource::Rsc(Rsc::new(storage, RSC_NAME, RSC_NAME)?)
         };
-        match res {
+        // another thread might have won the race, then its resource is used
+        Ok(self.res.get_or_init(|| res))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Cow<'_, [u8]>, Error> {
+        match self.res()? {
             AudioResource::Rsc(rsc) => rsc.get(id.parse::<u32>().map_err(|_| Error::InvalidIndex)?),
             AudioResource::Nrsc(nrsc) => nrsc.get(id),
         }
     }
 
-    pub fn get_by_idx(&mut self, idx: usize) -> Result<(AudioId<'_>, &[u8]), Error> {
-        self.init()?;
-        let Some(res) = self.res.as_mut() else {
-            unreachable!()
-        };
-        Ok(match res {
+    pub fn get_by_idx(&self, idx: usize) -> Result<(AudioId<'_>, Cow<'_, [u8]>), Error> {
+        Ok(match self.res()? {
             AudioResource::Rsc(rsc) => {
                 let (id, page) = rsc.get_by_idx(idx)?;
                 (AudioId::Num(id), page)
@@ -67,12 +79,8 @@ impl Audio {
         })
     }
 
-    pub fn idx_iter(&mut self) -> Result<Range<usize>, Error> {
-        self.init()?;
-        let Some(res) = self.res.as_ref() else {
-            unreachable!()
-        };
-        Ok(0..match res {
+    pub fn idx_iter(&self) -> Result<Range<usize>, Error> {
+        Ok(0..match self.res()? {
             AudioResource::Rsc(rsc) => rsc.len(),
             AudioResource::Nrsc(nrsc) => nrsc.len(),
         })
@@ -0,0 +1,453 @@
+//! `accent-dict`, the dictionary operations of the library for scripts and batch jobs.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+};
+
+use accent_dict::{
+    AnkiWriter, Column, CsvWriter, Error, HtmlRenderer, Keys, MonokakidoDict, PageItemId, PageIter,
+    Product, TextRenderer, YomitanWriter, coverage, gen_svg::gen_svg, try_parse_xml_lenient,
+    verify, write_jsonl, write_sqlite,
+};
+
+const USAGE: &str = "\
+usage: accent-dict [--dict DIR] <command> [args]
+
+commands:
+  lookup <word> [--type TYPE] [--html|--json|--ascii]
+                        entries of a word, TYPE is headword, compound or counter
+  search (--prefix P|--suffix S|--pattern P) [--type TYPE]
+                        words of the key indexes, patterns take ? and *
+  show <page> [--xml|--html|--json|--ascii]
+                        a page by its id
+  audio <id> [-o FILE]  an audio clip, to stdout without -o
+  svg <accent>          the pitch diagram of an accent like ア＼メ
+  export <format> [-o FILE] [--title T] [--columns C,...]
+                        jsonl, csv, tsv, sqlite, yomitan or anki; sqlite,
+                        yomitan and anki need -o
+  verify                checks all pages, keys and audio clips
+  coverage              how many pages the grammar parses, and the elements
+                        it doesn't know
+  info                  title, identifier and parts of the dictionary
+
+DIR is a Monokakido product or content directory, or a zip archive of one,
+assets/ by default. Pages of dictionaries other than the NHK accent dictionary
+are shown as XML.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    exit_code(run(args))
+}
+
+/// 2 for usage errors, which also print the usage.
+fn exit_code(result: Result<ExitCode, Error>) -> ExitCode {
+    match result {
+        Ok(code) => code,
+        Err(e @ (Error::InvalidSubcommand | Error::InvalidArg)) => {
+            eprintln!("error: {e:?}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("error: {e:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let dict = take_option(&mut args, "--dict")?.unwrap_or_else(|| "assets/".to_string());
+    if args.is_empty() {
+        return Err(Error::InvalidSubcommand);
+    }
+    let command = args.remove(0);
+    match command.as_str() {
+        "lookup" => lookup(&dict, args),
+        "search" => search(&dict, args),
+        "show" => show(&dict, args),
+        "audio" => audio(&dict, args),
+        "svg" => {
+            let [accent] = positional(args)?;
+            println!("{}", gen_svg(&accent).trim_end());
+            Ok(ExitCode::SUCCESS)
+        }
+        "export" => export(&dict, args),
+        "coverage" => {
+            positional::<0>(args)?;
+            let coverage = coverage(&dict)?;
+            print!("{coverage}");
+            Ok(
+                if coverage.failed.is_empty() && coverage.unreadable.is_empty() {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                },
+            )
+        }
+        "info" => {
+            positional::<0>(args)?;
+            info(&MonokakidoDict::open_with_path(&dict)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        "verify" => {
+            positional::<0>(args)?;
+            let report = verify(&dict);
+            for problem in &report.problems {
+                println!("{problem}");
+            }
+            eprintln!(
+                "{} pages, {} keys, {} audio clips, {} problems",
+                report.pages,
+                report.keys,
+                report.audio,
+                report.problems.len()
+            );
+            Ok(if report.is_ok() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            })
+        }
+        _ => Err(Error::InvalidSubcommand),
+    }
+}
+
+fn lookup(dict: &str, mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let format = Format::take(&mut args)?;
+    let key_types = take_key_types(&mut args)?;
+    let [word] = positional(args)?;
+    let dict = MonokakidoDict::open_with_path(dict)?;
+
+    let mut pages = Vec::new();
+    for keys in key_types.iter().map(|t| t.keys(&dict)) {
+        match keys.search_exact(&word) {
+            Ok((_, ids)) => pages.extend(ids.map(|id| id.page)),
+            Err(Error::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    pages.sort();
+    pages.dedup();
+    if pages.is_empty() {
+        eprintln!("{word}: not found");
+        return Ok(ExitCode::FAILURE);
+    }
+    for page in pages {
+        print_page(&dict, page, format)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+type Search = for<'a> fn(&'a Keys, &str) -> Result<Vec<(&'a str, PageIter<'a>)>, Error>;
+
+fn search(dict: &str, mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let key_types = take_key_types(&mut args)?;
+    let prefix = take_option(&mut args, "--prefix")?;
+    let suffix = take_option(&mut args, "--suffix")?;
+    let pattern = take_option(&mut args, "--pattern")?;
+    positional::<0>(args)?;
+    let (search, word) = match (prefix, suffix, pattern) {
+        (Some(prefix), None, None) => (Keys::search_prefix as Search, prefix),
+        (None, Some(suffix), None) => (Keys::search_suffix as Search, suffix),
+        (None, None, Some(pattern)) => (Keys::search_pattern as Search, pattern),
+        _ => return Err(Error::InvalidArg),
+    };
+    let dict = MonokakidoDict::open_with_path(dict)?;
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    for key_type in key_types {
+        for (found, ids) in search(key_type.keys(&dict), &word)? {
+            let pages: Vec<String> = ids.map(|id| format!("{:05}", id.page)).collect();
+            writeln!(out, "{found}\t{}\t{}", key_type.name(), pages.join(","))?;
+        }
+    }
+    out.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn show(dict: &str, mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let format = Format::take(&mut args)?;
+    let [page] = positional(args)?;
+    let page = page.parse().map_err(|_| Error::InvalidArg)?;
+    let dict = MonokakidoDict::open_with_path(dict)?;
+    print_page(&dict, page, format)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn audio(dict: &str, mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let out = take_option(&mut args, "-o")?;
+    let [id] = positional(args)?;
+    let id = id.strip_suffix(".aac").unwrap_or(&id);
+    let dict = MonokakidoDict::open_with_path(dict)?;
+    if !dict.info.audio {
+        return Err(Error::MissingAudio);
+    }
+    let data = dict.audio.get(id)?;
+    match out {
+        Some(out) => fs::write(out, &data)?,
+        None => io::stdout().lock().write_all(&data)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn export(dict: &str, mut args: Vec<String>) -> Result<ExitCode, Error> {
+    let out = take_option(&mut args, "-o")?;
+    let title = take_option(&mut args, "--title")?;
+    let columns = match take_option(&mut args, "--columns")? {
+        Some(columns) => columns
+            .split(',')
+            .map(|c| c.trim().parse())
+            .collect::<Result<Vec<Column>, _>>()?,
+        None => Column::ALL.to_vec(),
+    };
+    let [format] = positional(args)?;
+    let title = title.as_deref().unwrap_or("NHK アクセント");
+
+    let exported = match (format.as_str(), out) {
+        ("jsonl", out) => write_jsonl(dict, writer(out)?)?,
+        ("csv", out) => CsvWriter::new(&columns).write(dict, writer(out)?)?,
+        ("tsv", out) => CsvWriter::tsv(&columns).write(dict, writer(out)?)?,
+        ("sqlite", Some(out)) => write_sqlite(dict, out.as_ref())?,
+        ("yomitan", Some(out)) => YomitanWriter::new(title).write(dict, File::create(out)?)?,
+        ("anki", Some(out)) => AnkiWriter::new(title).write(dict, File::create(out)?, |_| true)?,
+        ("sqlite" | "yomitan" | "anki", None) => return Err(Error::InvalidArg),
+        _ => return Err(Error::InvalidSubcommand),
+    };
+    for page in &exported.skipped {
+        eprintln!("skipped {page}");
+    }
+    eprintln!("{} exported", exported.count);
+    Ok(ExitCode::SUCCESS)
+}
+
+fn info(dict: &MonokakidoDict) {
+    let info = &dict.info;
+    let yes_no = |b| if b { "yes" } else { "no" };
+    let product = match info.product {
+        Product::NhkAccent => "NHK accent",
+        Product::Other => "other",
+    };
+    println!("title\t{}", info.title.as_deref().unwrap_or("-"));
+    println!("identifier\t{}", info.identifier.as_deref().unwrap_or("-"));
+    println!("product\t{product}");
+    println!("audio\t{}", yes_no(info.audio));
+    println!("headlines\t{}", yes_no(info.headlines));
+    println!("keys\t{}", info.key_indexes.join(","));
+}
+
+/// A file, or stdout without one.
+fn writer(out: Option<String>) -> Result<Box<dyn Write>, Error> {
+    Ok(match out {
+        Some(out) => Box::new(BufWriter::new(File::create(out)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Text { ascii: bool },
+    Xml,
+    Html,
+    Json,
+}
+
+impl Format {
+    fn take(args: &mut Vec<String>) -> Result<Self, Error> {
+        let flags = [
+            take_flag(args, "--xml"),
+            take_flag(args, "--html"),
+            take_flag(args, "--json"),
+            take_flag(args, "--ascii"),
+        ];
+        Ok(match flags {
+            [false, false, false, ascii] => Format::Text { ascii },
+            [true, false, false, false] => Format::Xml,
+            [false, true, false, false] => Format::Html,
+            [false, false, true, false] => Format::Json,
+            _ => return Err(Error::InvalidArg),
+        })
+    }
+}
+
+fn print_page(dict: &MonokakidoDict, page: u32, format: Format) -> Result<(), Error> {
+    let xml = dict.pages.get_page(PageItemId { page, item: 0 })?;
+    // only the NHK pages parse
+    if matches!(format, Format::Xml) || dict.info.product != Product::NhkAccent {
+        print!("{xml}");
+        return Ok(());
+    }
+    let item = try_parse_xml_lenient(&xml).map_err(|e| {
+        eprintln!("page {page}: {e}");
+        Error::XmlError
+    })?;
+    match format {
+        Format::Text { ascii } => {
+            let mut renderer = TextRenderer::new();
+            renderer.set_ascii(ascii);
+            print!("{}", renderer.render(&item));
+        }
+        Format::Html => println!("{}", HtmlRenderer::new().render(&item)),
+        Format::Json => {
+            println!(
+                "{}",
+                serde_json::to_string(&item).map_err(|_| Error::FmtError)?
+            )
+        }
+        Format::Xml => unreachable!(),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum KeyType {
+    Headword,
+    Compound,
+    Counter,
+}
+
+impl KeyType {
+    const ALL: [KeyType; 3] = [KeyType::Headword, KeyType::Compound, KeyType::Counter];
+
+    fn name(self) -> &'static str {
+        match self {
+            KeyType::Headword => "headword",
+            KeyType::Compound => "compound",
+            KeyType::Counter => "counter",
+        }
+    }
+
+    fn keys(self, dict: &MonokakidoDict) -> &Keys {
+        match self {
+            KeyType::Headword => &dict.headword_keys,
+            KeyType::Compound => &dict.compound_keys,
+            KeyType::Counter => &dict.numeral_keys,
+        }
+    }
+}
+
+/// The key indexes `--type` selects, all of them without it.
+fn take_key_types(args: &mut Vec<String>) -> Result<Vec<KeyType>, Error> {
+    let Some(name) = take_option(args, "--type")? else {
+        return Ok(KeyType::ALL.to_vec());
+    };
+    let key_type = KeyType::ALL
+        .into_iter()
+        .find(|t| t.name() == name)
+        .ok_or(Error::InvalidArg)?;
+    Ok(vec![key_type])
+}
+
+/// Removes `name` and the value after it from `args`.
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, Error> {
+    let Some(pos) = args.iter().position(|a| a == name) else {
+        return Ok(None);
+    };
+    if pos + 1 >= args.len() {
+        return Err(Error::InvalidArg);
+    }
+    let value = args.remove(pos + 1);
+    args.remove(pos);
+    Ok(Some(value))
+}
+
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let Some(pos) = args.iter().position(|a| a == name) else {
+        return false;
+    };
+    args.remove(pos);
+    true
+}
+
+/// The remaining arguments, which have to be exactly `N` and no options.
+fn positional<const N: usize>(args: Vec<String>) -> Result<[String; N], Error> {
+    if args.iter().any(|a| a.starts_with('-') && a.len() > 1) {
+        return Err(Error::InvalidArg);
+    }
+    args.try_into().map_err(|_| Error::InvalidArg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // argument errors are found before the dictionary is read
+    const NO_DICT: &str = "/nonexistent/accent-dict";
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn options_and_flags() {
+        let mut a = args(&["lookup", "--type", "counter", "--json", "いっぽん"]);
+        assert_eq!(
+            take_option(&mut a, "--type"),
+            Ok(Some("counter".to_string()))
+        );
+        assert_eq!(take_option(&mut a, "--type"), Ok(None));
+        assert!(take_flag(&mut a, "--json"));
+        assert_eq!(a, ["lookup", "いっぽん"]);
+
+        let mut a = args(&["audio", "-o"]);
+        assert_eq!(take_option(&mut a, "-o"), Err(Error::InvalidArg));
+    }
+
+    #[test]
+    fn formats() {
+        let mut a = args(&["--ascii", "1"]);
+        assert!(matches!(
+            Format::take(&mut a),
+            Ok(Format::Text { ascii: true })
+        ));
+        assert_eq!(a, ["1"]);
+        assert!(matches!(
+            Format::take(&mut args(&["--xml"])),
+            Ok(Format::Xml)
+        ));
+        for flags in [&["--xml", "--json"][..], &["--html", "--ascii"]] {
+            assert_eq!(
+                Format::take(&mut args(flags)).err(),
+                Some(Error::InvalidArg)
+            );
+        }
+    }
+
+    #[test]
+    fn positional_args() {
+        assert_eq!(positional(args(&["1"])), Ok(["1".to_string()]));
+        // a lone dash is a value
+        assert_eq!(positional(args(&["-"])), Ok(["-".to_string()]));
+        assert_eq!(positional::<1>(args(&["1", "2"])), Err(Error::InvalidArg));
+        assert_eq!(positional::<1>(args(&["--jsn"])), Err(Error::InvalidArg));
+        assert_eq!(positional::<0>(args(&["-x"])), Err(Error::InvalidArg));
+    }
+
+    #[test]
+    fn search_needs_one_query() {
+        for query in [
+            &[][..],
+            &["--prefix", "サ", "--suffix", "ラ"],
+            &["--prefix", "サ", "--pattern", "サ*"],
+        ] {
+            assert_eq!(search(NO_DICT, args(query)), Err(Error::InvalidArg));
+        }
+    }
+
+    #[test]
+    fn usage_errors() {
+        let run_args = |a: &[&str]| run(args(&[&["--dict", NO_DICT][..], a].concat()));
+        assert_eq!(run_args(&["export", "sqlite"]), Err(Error::InvalidArg));
+        assert_eq!(run_args(&["export", "xml"]), Err(Error::InvalidSubcommand));
+        assert_eq!(run_args(&["frobnicate"]), Err(Error::InvalidSubcommand));
+        assert_eq!(
+            exit_code(run_args(&["export", "sqlite"])),
+            ExitCode::from(2)
+        );
+        assert_eq!(exit_code(Err(Error::IOError)), ExitCode::FAILURE);
+    }
+}
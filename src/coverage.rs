@@ -0,0 +1,242 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
+
+use crate::{
+    Accent, AccentText, BodyContent, ConTableContent, DicItem, Error, H, Head, HeadG, Indent,
+    Josushi, NoteContent, Notes, Pages, ParseError, Unknown, try_parse_xml, try_parse_xml_lenient,
+};
+
+// page ids kept per unknown class
+const EXAMPLE_PAGES: usize = 5;
+
+/// Result of `coverage`.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    pub pages: usize,
+    /// Pages the strict grammar parses.
+    pub strict: usize,
+    /// Pages that only parse in lenient mode.
+    pub lenient: usize,
+    /// Pages that don't parse in lenient mode either, by page id.
+    pub failed: Vec<(u32, ParseError)>,
+    /// Positions in the contents index of pages that can't be read.
+    pub unreadable: Vec<(usize, Error)>,
+    /// Unknown elements of the lenient parses, by `tag.class`.
+    pub unknown: BTreeMap<String, UnknownClass>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UnknownClass {
+    /// How often an element of the class was found.
+    pub count: usize,
+    /// The first few pages it was found in.
+    pub pages: Vec<u32>,
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pages:        {}", self.pages)?;
+        writeln!(f, "strict:       {}", self.strict)?;
+        writeln!(f, "lenient only: {}", self.lenient)?;
+        writeln!(f, "failed:       {}", self.failed.len())?;
+        writeln!(f, "unreadable:   {}", self.unreadable.len())?;
+        if !self.unknown.is_empty() {
+            writeln!(f, "unknown elements:")?;
+        }
+        let mut unknown: Vec<_> = self.unknown.iter().collect();
+        unknown.sort_by_key(|(_, class)| Reverse(class.count));
+        for (class, UnknownClass { count, pages }) in unknown {
+            let pages: Vec<String> = pages.iter().map(u32::to_string).collect();
+            writeln!(f, "  {count:6} {class} (pages {})", pages.join(", "))?;
+        }
+        for (page, error) in &self.failed {
+            writeln!(f, "page {page}: {error}")?;
+        }
+        for (idx, error) in &self.unreadable {
+            writeln!(f, "page #{idx}: can't read ({error:?})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses every page of the dictionary at `path`, strictly and, where that fails,
+/// leniently, and collects the elements the grammar doesn't know.
+pub fn coverage(path: &str) -> Result<Coverage, Error> {
+    let pages = Pages::new(path)?;
+    pages.init()?;
+
+    let mut coverage = Coverage::default();
+    for idx in pages.idx_iter()? {
+        coverage.pages += 1;
+        let (id, xml) = match pages.page_by_idx(idx) {
+            Ok(page) => page,
+            Err(error) => {
+                coverage.unreadable.push((idx, error));
+                continue;
+            }
+        };
+        if try_parse_xml(&xml).is_ok() {
+            coverage.strict += 1;
+            continue;
+        }
+        match try_parse_xml_lenient(&xml) {
+            Ok(item) => {
+                coverage.lenient += 1;
+                for unknown in unknown_elements(&item) {
+                    coverage.add_unknown(id, unknown);
+                }
+            }
+            Err(error) => coverage.failed.push((id, error)),
+        }
+    }
+    Ok(coverage)
+}
+
+impl Coverage {
+    fn add_unknown(&mut self, page: u32, unknown: &Unknown) {
+        let key = match &unknown.class {
+            Some(class) => format!("{}.{class}", unknown.tag),
+            None => unknown.tag.clone(),
+        };
+        let class = self.unknown.entry(key).or_default();
+        class.count += 1;
+        if class.pages.len() < EXAMPLE_PAGES && class.pages.last() != Some(&page) {
+            class.pages.push(page);
+        }
+    }
+}
+
+/// The `Unknown` nodes the lenient grammar put in place of known ones. Their
+/// children are not listed, they are part of the unknown markup.
+fn unknown_elements(item: &DicItem) -> Vec<&Unknown> {
+    let mut unknowns = Vec::new();
+    for HeadG(head, body) in &item.1 {
+        if let Head::H(h) = head {
+            unknowns.extend(h.iter().filter_map(|h| match h {
+                H::Unknown(unknown) => Some(unknown),
+                _ => None,
+            }));
+        }
+        for content in &body.0 {
+            match content {
+                BodyContent::Unknown(unknown) => unknowns.push(unknown),
+                BodyContent::Accent(accents) => unknowns.extend(accent_unknowns(accents)),
+                BodyContent::ConTable(table) => {
+                    for content in table {
+                        if let ConTableContent::Accent(accents) = content {
+                            unknowns.extend(accent_unknowns(accents));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    for Josushi(_, accents, indents, notes) in &item.2 {
+        unknowns.extend(accent_unknowns(accents));
+        for Indent(texts) in indents {
+            unknowns.extend(text_unknowns(texts));
+        }
+        let notes = notes.iter().flat_map(|Notes(notes)| notes);
+        for content in notes.flat_map(|(_, contents)| contents) {
+            if let NoteContent::Accent(Accent(_, texts)) = content {
+                unknowns.extend(text_unknowns(texts));
+            }
+        }
+    }
+    unknowns
+}
+
+fn accent_unknowns(accents: &[Accent]) -> impl Iterator<Item = &Unknown> {
+    accents
+        .iter()
+        .flat_map(|Accent(_, texts)| text_unknowns(texts))
+}
+
+fn text_unknowns(texts: &[AccentText]) -> impl Iterator<Item = &Unknown> {
+    texts.iter().filter_map(|text| match text {
+        AccentText::Unknown(unknown) => Some(unknown),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        RscWriter,
+        fixture::{build_dict, page_xml},
+    };
+
+    #[test]
+    fn strict_dict() {
+        let dir = build_dict("coverage_strict");
+        let coverage = coverage(dir.to_str().unwrap()).unwrap();
+        assert_eq!(coverage.pages, 4);
+        assert_eq!(coverage.strict, 4);
+        assert_eq!(coverage.lenient, 0);
+        assert!(coverage.failed.is_empty());
+        assert!(coverage.unknown.is_empty());
+    }
+
+    #[test]
+    fn unknown_classes() {
+        let dir = build_dict("coverage_unknown");
+        let contents = dir.join("contents");
+        fs::remove_dir_all(&contents).unwrap();
+        fs::create_dir(&contents).unwrap();
+
+        let note = "<span class=\"pitch_note\">中高</span>";
+        let body_end = "</div></span></span></body>";
+        let head_end = "</span></div><div class=\"body\">";
+        let with_note =
+            |id, headword| page_xml(id, headword).replace(body_end, &format!("{note}{body_end}"));
+        let mut writer = RscWriter::new(&contents, "contents");
+        writer.add(1, page_xml(1, "さくら").as_bytes()).unwrap();
+        // in the body and inside the accent text
+        let page = with_note(2, "あめ").replacen("ア", &format!("ア{note}"), 1);
+        writer.add(2, page.as_bytes()).unwrap();
+        // inside the accents of a con_table and of a counter note
+        let accent = |kana| {
+            format!("<span class=\"accent\"><span class=\"accent_text\">{kana}{note}</span></span>")
+        };
+        let con_table = format!("<span class=\"con_table\">{}</span>", accent("ホン"));
+        let josushi_note = format!("<span class=\"note\">{}</span>", accent("イッポン"));
+        let page = page_xml(3, "―ほん")
+            .replace(
+                "</div></span><div class=\"josushi\">",
+                &format!("{con_table}</div></span><div class=\"josushi\">"),
+            )
+            .replace(
+                "</div></span></body>",
+                &format!("{josushi_note}</div></span></body>"),
+            );
+        writer.add(3, page.as_bytes()).unwrap();
+        // in the headword and twice in the body
+        let page = with_note(4, "はなみ")
+            .replace(head_end, &format!("{note}{head_end}"))
+            .replace(body_end, &format!("<div class=\"extra\"/>{body_end}"));
+        writer.add(4, page.as_bytes()).unwrap();
+        let page = page_xml(5, "ゆき").replace("<body>", "<body><p/>");
+        writer.add(5, page.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let coverage = coverage(dir.to_str().unwrap()).unwrap();
+        assert_eq!(coverage.pages, 5);
+        assert_eq!(coverage.strict, 1);
+        assert_eq!(coverage.lenient, 3);
+        assert_eq!(coverage.failed.len(), 1);
+        assert_eq!(coverage.failed[0].0, 5);
+        assert_eq!(
+            coverage.unknown["span.pitch_note"],
+            UnknownClass {
+                count: 6,
+                pages: vec![2, 3, 4]
+            }
+        );
+        assert_eq!(coverage.unknown["div.extra"].pages, [4]);
+        let report = coverage.to_string();
+        assert!(report.contains("     6 span.pitch_note (pages 2, 3, 4)"));
+    }
+}
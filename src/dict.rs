@@ -1,6 +1,21 @@
-use crate::{Error, audio::Audio, key::Keys, pages::Pages};
+use std::{borrow::Cow, path::Path, sync::Arc};
+
+use crate::{
+    DicItem, Error, LoadMode, WordType,
+    audio::Audio,
+    info::{DictInfo, detect},
+    key::Keys,
+    pages::Pages,
+    storage::{Storage, open_path},
+    try_parse_xml_lenient,
+};
+
+pub(crate) const HEADWORD_KEYS: &str = "headword.keyindex";
+pub(crate) const COMPOUND_KEYS: &str = "compound.keyindex";
+pub(crate) const NUMERAL_KEYS: &str = "numeral.keyindex";
 
 pub struct MonokakidoDict {
+    pub info: DictInfo,
     pub pages: Pages,
     pub audio: Audio,
     pub headword_keys: Keys,
@@ -13,13 +28,33 @@ impl MonokakidoDict {
         Self::open_with_path("assets/")
     }
 
+    /// The dictionary at `path`: a product directory, a content directory like
+    /// the add-on's `assets/`, or a zip archive of either.
     pub fn open_with_path(path: &str) -> Result<Self, Error> {
-        let pages = Pages::new(path)?;
-        let audio = Audio::new(path)?;
-        let headword_keys = Keys::new(path, "headword.keyindex")?;
-        let compound_keys = Keys::new(path, "compound.keyindex")?;
-        let numeral_keys = Keys::new(path, "numeral.keyindex")?;
+        Self::open_with_mode(path, LoadMode::default())
+    }
+
+    pub fn open_with_mode(path: &str, mode: LoadMode) -> Result<Self, Error> {
+        Self::open_with_storage(open_path(Path::new(path), mode)?)
+    }
+
+    /// Key indexes the dictionary doesn't have are empty.
+    pub fn open_with_storage(storage: Arc<dyn Storage>) -> Result<Self, Error> {
+        let (info, storage) = detect(storage)?;
+        let keys = |name| {
+            if info.has_key_index(name) {
+                Keys::with_storage(&*storage, name)
+            } else {
+                Ok(Keys::empty())
+            }
+        };
+        let headword_keys = keys(HEADWORD_KEYS)?;
+        let compound_keys = keys(COMPOUND_KEYS)?;
+        let numeral_keys = keys(NUMERAL_KEYS)?;
+        let pages = Pages::with_storage(storage.clone())?;
+        let audio = Audio::with_storage(storage)?;
         Ok(MonokakidoDict {
+            info,
             pages,
             audio,
             headword_keys,
@@ -27,4 +62,68 @@ impl MonokakidoDict {
             numeral_keys,
         })
     }
+
+    /// The parsed pages of `vocab`, a word or a page index, see `find_pages`.
+    /// Empty if there is no such word or page.
+    pub fn look_up(&self, vocab: &str, word_type: Option<WordType>) -> Result<Vec<DicItem>, Error> {
+        parse_pages(find_pages(self, vocab, word_type)?)
+    }
+}
+
+/// The pages `vocab` refers to: a page index if it starts with digits, otherwise
+/// a word looked up in the key indexes of `word_type`. Empty if no key has the
+/// word or there is no page at the index.
+pub(crate) fn find_pages<'a>(
+    dict: &'a MonokakidoDict,
+    vocab: &str,
+    word_type: Option<WordType>,
+) -> Result<Vec<(u32, Cow<'a, str>)>, Error> {
+    // is dict index
+    if vocab.starts_with(|c: char| c.is_ascii_digit()) {
+        let digits: String = vocab.chars().take_while(char::is_ascii_digit).collect();
+        //I don't know why it is off by one
+        let index = digits.parse::<usize>().map(|index| index.saturating_sub(1));
+        return match index {
+            Ok(index) if index < dict.pages.idx_iter()?.len() => {
+                Ok(vec![dict.pages.page_by_idx(index)?])
+            }
+            _ => Ok(Vec::new()),
+        };
+    }
+
+    // is vocab
+    let mut pages = Vec::new();
+
+    let search_headword = word_type.is_none() || word_type == Some(WordType::HEADWORD);
+    let search_compound = word_type.is_none() || word_type == Some(WordType::COMPOUND);
+    let search_numeral = word_type.is_none() || word_type == Some(WordType::COUNTER);
+
+    if search_headword && let Ok((_, hw_pages)) = dict.headword_keys.search_exact(vocab) {
+        pages.push(hw_pages);
+    }
+    if search_compound && let Ok((_, compound_pages)) = dict.compound_keys.search_exact(vocab) {
+        pages.push(compound_pages);
+    }
+    if search_numeral && let Ok((_, numeral_pages)) = dict.numeral_keys.search_exact(vocab) {
+        pages.push(numeral_pages);
+    }
+    pages
+        .into_iter()
+        .flatten()
+        .map(|id| Ok((id.page, dict.pages.get_page(id)?)))
+        .collect()
+}
+
+/// Parses the pages leniently, `Error::XmlError` if one of them doesn't parse.
+pub(crate) fn parse_pages(pages: Vec<(u32, Cow<'_, str>)>) -> Result<Vec<DicItem>, Error> {
+    pages
+        .into_iter()
+        .map(|(_, page)| try_parse_xml_lenient(&page).map_err(|_| Error::XmlError))
+        .collect()
+}
+
+#[test]
+fn test_dict_is_sync() {
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<MonokakidoDict>();
 }
@@ -21,12 +21,14 @@ pub enum Error {
     MissingResourceFile,
     InvalidIndex,
     InvalidAudioFormat,
+    AudioDecodeError,
     InvalidArg,
     FmtError,
     IndexDoesntExist,
     XmlError,
     MissingAudio,
     InvalidSubcommand,
+    SqliteError,
 }
 
 impl From<IoError> for Error {
@@ -52,3 +54,15 @@ impl From<xmlparser::Error> for Error {
         Error::XmlError
     }
 }
+
+impl From<zip::result::ZipError> for Error {
+    fn from(_: zip::result::ZipError) -> Self {
+        Error::IOError
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(_: rusqlite::Error) -> Self {
+        Error::SqliteError
+    }
+}
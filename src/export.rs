@@ -0,0 +1,176 @@
+//! Dictionary entries in a flat shape that doesn't depend on the page markup,
+//! and the exporters writing them.
+
+mod anki;
+mod csv;
+mod json;
+mod sqlite;
+mod yomitan;
+
+use std::fmt;
+
+use serde::Serialize;
+
+pub use anki::AnkiWriter;
+pub use csv::{Column, CsvWriter};
+pub use json::{JSON_VERSION, write_jsonl};
+pub use sqlite::write_sqlite;
+pub use yomitan::YomitanWriter;
+
+use crate::{
+    DicItem, Error, Keys, MonokakidoDict, Pages, ParseError, Unpacked, pitch::parse_pitch,
+    try_parse_xml_lenient, unpack_dic_item,
+};
+
+/// A headword, or a counter form, of a dictionary page with its accents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    /// Id of the page in the contents.
+    pub page: u32,
+    /// Id of the entry, the page id for headwords, an anchor like `00003-0001`
+    /// for counter forms.
+    pub id: String,
+    /// Kana headword, for counter forms the number as printed.
+    pub reading: String,
+    /// Headline as displayed, with kanji and brackets.
+    pub head: String,
+    pub kanji: Option<String>,
+    pub accents: Vec<EntryAccent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryAccent {
+    /// Kana with the accent marks `＼` (drop) and `▔` (flat).
+    pub accent: String,
+    /// Id of the audio clip, as `Audio::get` takes it.
+    pub audio: Option<String>,
+}
+
+/// What an exporter wrote, and the pages it had to leave out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Exported {
+    /// Entries, rows or notes written, depending on the format.
+    pub count: usize,
+    pub skipped: Vec<SkippedPage>,
+}
+
+/// A page missing from an export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkippedPage {
+    /// The page at this position of the contents index can't be read.
+    Unreadable { idx: usize, error: Error },
+    /// The page with this id doesn't parse.
+    Unparsable { page: u32, error: ParseError },
+}
+
+impl fmt::Display for SkippedPage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkippedPage::Unreadable { idx, error } => write!(f, "page #{idx} ({error:?})"),
+            SkippedPage::Unparsable { page, error } => write!(f, "page {page}: {error}"),
+        }
+    }
+}
+
+/// The key indexes of the dictionary at `path` and the word type of the words
+/// in them. Key indexes the dictionary doesn't have are empty.
+fn key_indexes(path: &str) -> Result<[(Keys, &'static str); 3], Error> {
+    let dict = MonokakidoDict::open_with_path(path)?;
+    Ok([
+        (dict.headword_keys, "headword"),
+        (dict.compound_keys, "compound"),
+        (dict.numeral_keys, "counter"),
+    ])
+}
+
+/// Downstep, length and class of a one word accent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AccentShape {
+    /// Morae before the drop, 0 for heiban.
+    downstep: usize,
+    morae: usize,
+    class: &'static str,
+}
+
+impl AccentShape {
+    /// None for accents of several words, or without a reading.
+    fn new(accent: &str) -> Option<Self> {
+        let [word] = &parse_pitch(accent)[..] else {
+            return None;
+        };
+        let morae = word.morae.len();
+        let class = match word.downstep {
+            None => "heiban",
+            Some(1) => "atamadaka",
+            Some(d) if d == morae => "odaka",
+            Some(_) => "nakadaka",
+        };
+        Some(AccentShape {
+            downstep: word.downstep.unwrap_or(0),
+            morae,
+            class,
+        })
+    }
+}
+
+impl Entry {
+    fn new(page: u32, unpacked: Unpacked) -> Self {
+        let accents = unpacked
+            .pron
+            .into_iter()
+            .map(|pron| EntryAccent {
+                accent: pron.accent,
+                audio: pron
+                    .sound_file
+                    .map(|s| s.strip_suffix(".aac").unwrap_or(&s).to_string()),
+            })
+            .collect();
+        Entry {
+            page,
+            id: unpacked.id,
+            reading: unpacked.reading,
+            head: unpacked.head.trim_end().to_string(),
+            kanji: unpacked.kanji,
+            accents,
+        }
+    }
+}
+
+/// Calls `f` with the entries of every page of the dictionary at `path`, in the
+/// order of the contents. Pages that can't be read or parsed are skipped and
+/// returned.
+pub fn for_each_entry(
+    path: &str,
+    mut f: impl FnMut(Entry) -> Result<(), Error>,
+) -> Result<Vec<SkippedPage>, Error> {
+    for_each_page(path, |id, item| {
+        for unpacked in unpack_dic_item(item) {
+            f(Entry::new(id, unpacked))?;
+        }
+        Ok(())
+    })
+}
+
+/// Like `for_each_entry`, with the parsed pages and their ids.
+fn for_each_page(
+    path: &str,
+    mut f: impl FnMut(u32, DicItem) -> Result<(), Error>,
+) -> Result<Vec<SkippedPage>, Error> {
+    let pages = Pages::new(path)?;
+    pages.init()?;
+    let mut skipped = Vec::new();
+    for idx in pages.idx_iter()? {
+        let (id, xml) = match pages.page_by_idx(idx) {
+            Ok(page) => page,
+            Err(error) => {
+                skipped.push(SkippedPage::Unreadable { idx, error });
+                continue;
+            }
+        };
+        match try_parse_xml_lenient(&xml) {
+            Ok(item) => f(id, item)?,
+            Err(error) => skipped.push(SkippedPage::Unparsable { page: id, error }),
+        }
+    }
+    Ok(skipped)
+}
@@ -0,0 +1,500 @@
+//! Anki deck package (`.apkg`): a zip with the collection as SQLite database in
+//! the `collection.anki2` layout every Anki version imports, a `media` file
+//! mapping the numbered media files to their names, and the media files.
+//!
+//! Notes use a note type with the fields the add-on fills: `dict`, `voc`,
+//! `pitch` and `audio`.
+
+use std::{
+    collections::HashMap,
+    io::{Seek, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{Connection, params};
+use serde_json::json;
+use sha1_smol::Sha1;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use super::{Entry, Exported, for_each_entry};
+use crate::{Error, MonokakidoDict, gen_svg::gen_svg};
+
+// fixed, so notes of several exported decks share one note type
+const MODEL_ID: i64 = 1_700_000_000_042;
+const MODEL_NAME: &str = "accent_dict";
+const FIELDS: [&str; 4] = ["dict", "voc", "pitch", "audio"];
+// same prefix as the add-on, so diagrams are shared with notes it filled
+const SVG_PREFIX: &str = "pitch_v2_";
+const DEFAULT_DECK_ID: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer PRIMARY KEY, crt integer NOT NULL, mod integer NOT NULL,
+    scm integer NOT NULL, ver integer NOT NULL, dty integer NOT NULL,
+    usn integer NOT NULL, ls integer NOT NULL, conf text NOT NULL,
+    models text NOT NULL, decks text NOT NULL, dconf text NOT NULL, tags text NOT NULL
+);
+CREATE TABLE notes (
+    id integer PRIMARY KEY, guid text NOT NULL, mid integer NOT NULL,
+    mod integer NOT NULL, usn integer NOT NULL, tags text NOT NULL,
+    flds text NOT NULL, sfld integer NOT NULL, csum integer NOT NULL,
+    flags integer NOT NULL, data text NOT NULL
+);
+CREATE TABLE cards (
+    id integer PRIMARY KEY, nid integer NOT NULL, did integer NOT NULL,
+    ord integer NOT NULL, mod integer NOT NULL, usn integer NOT NULL,
+    type integer NOT NULL, queue integer NOT NULL, due integer NOT NULL,
+    ivl integer NOT NULL, factor integer NOT NULL, reps integer NOT NULL,
+    lapses integer NOT NULL, left integer NOT NULL, odue integer NOT NULL,
+    odid integer NOT NULL, flags integer NOT NULL, data text NOT NULL
+);
+CREATE TABLE revlog (
+    id integer PRIMARY KEY, cid integer NOT NULL, usn integer NOT NULL,
+    ease integer NOT NULL, ivl integer NOT NULL, lastIvl integer NOT NULL,
+    factor integer NOT NULL, time integer NOT NULL, type integer NOT NULL
+);
+CREATE TABLE graves (usn integer NOT NULL, oid integer NOT NULL, type integer NOT NULL);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+/// Builds an Anki deck from the dictionary at a path, one note per accent.
+#[derive(Debug)]
+pub struct AnkiWriter {
+    deck: String,
+}
+
+/// A note and the media it refers to.
+struct Note {
+    fields: [String; 4],
+    svg: (String, String),
+    // media name, audio id
+    audio: Option<(String, String)>,
+}
+
+impl AnkiWriter {
+    pub fn new(deck: &str) -> Self {
+        AnkiWriter {
+            deck: deck.to_string(),
+        }
+    }
+
+    /// Writes the package with the accents of the entries `filter` accepts to
+    /// `out`, returns the number of notes. Notes of audio clips that can't be
+    /// read get no sound.
+    pub fn write(
+        &self,
+        path: &str,
+        out: impl Write + Seek,
+        mut filter: impl FnMut(&Entry) -> bool,
+    ) -> Result<Exported, Error> {
+        let mut notes = Vec::new();
+        let skipped = for_each_entry(path, |entry| {
+            if filter(&entry) {
+                notes.extend(entry_notes(&entry));
+            }
+            Ok(())
+        })?;
+
+        let audio = MonokakidoDict::open_with_path(path)?.audio;
+        // audio id -> clip
+        let mut clips = HashMap::new();
+        for note in &mut notes {
+            let Some((_, id)) = &note.audio else { continue };
+            if !clips.contains_key(id) {
+                match audio.get(id) {
+                    Ok(clip) => {
+                        clips.insert(id.clone(), clip);
+                    }
+                    Err(_) => {
+                        note.audio = None;
+                        note.fields[3].clear();
+                    }
+                }
+            }
+        }
+        let collection = self.collection(&notes)?;
+
+        let mut zip = ZipWriter::new(out);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file("collection.anki2", options)?;
+        zip.write_all(&collection)?;
+
+        // media name -> number of the file in the zip
+        let mut media: HashMap<&str, usize> = HashMap::new();
+        for note in &notes {
+            let (name, svg) = &note.svg;
+            if !media.contains_key(name.as_str()) {
+                zip.start_file(media.len().to_string(), options)?;
+                zip.write_all(svg.as_bytes())?;
+                media.insert(name, media.len());
+            }
+            if let Some((name, id)) = &note.audio
+                && !media.contains_key(name.as_str())
+            {
+                // AAC is compressed already
+                zip.start_file(media.len().to_string(), SimpleFileOptions::default())?;
+                zip.write_all(&clips[id])?;
+                media.insert(name, media.len());
+            }
+        }
+        let media: HashMap<String, &str> = media
+            .into_iter()
+            .map(|(name, n)| (n.to_string(), name))
+            .collect();
+        zip.start_file("media", options)?;
+        zip.write_all(&serde_json::to_vec(&media).map_err(|_| Error::IOError)?)?;
+        zip.finish()?;
+        Ok(Exported {
+            count: notes.len(),
+            skipped,
+        })
+    }
+
+    /// The collection database with the notes and a new card for each.
+    fn collection(&self, notes: &[Note]) -> Result<Vec<u8>, Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let (secs, millis) = (now.as_secs() as i64, now.as_millis() as i64);
+        let deck_id = deck_id(&self.deck);
+
+        let db = Connection::open_in_memory()?;
+        db.execute_batch(SCHEMA)?;
+        db.execute(
+            "INSERT INTO col VALUES (1, ?1, ?2, ?3, 11, 0, 0, 0, ?4, ?5, ?6, ?7, '{}')",
+            params![
+                secs - secs % 86_400,
+                secs,
+                millis,
+                conf(deck_id).to_string(),
+                models(deck_id, secs).to_string(),
+                decks(deck_id, &self.deck, secs).to_string(),
+                dconf().to_string(),
+            ],
+        )?;
+        for (n, note) in notes.iter().enumerate() {
+            let id = millis + n as i64;
+            let [dict, voc, ..] = &note.fields;
+            db.execute(
+                "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, '', ?5, ?6, ?7, 0, '')",
+                params![
+                    id,
+                    guid(dict),
+                    MODEL_ID,
+                    secs,
+                    note.fields.join("\x1f"),
+                    voc,
+                    checksum(voc),
+                ],
+            )?;
+            db.execute(
+                "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                params![id, deck_id, secs, n as i64 + 1],
+            )?;
+        }
+        Ok(db.serialize("main")?.to_vec())
+    }
+}
+
+/// Notes of the accents of `entry`, the fields filled the way the add-on fills them.
+fn entry_notes(entry: &Entry) -> Vec<Note> {
+    entry
+        .accents
+        .iter()
+        .enumerate()
+        .map(|(i, accent)| {
+            let svg_name = format!("{SVG_PREFIX}{}.svg", base32(accent.accent.as_bytes()));
+            let audio = accent
+                .audio
+                .as_ref()
+                .map(|id| (format!("{id}.aac"), id.clone()));
+            let sound = match &audio {
+                Some((name, _)) => format!("[sound:{name}]"),
+                None => String::new(),
+            };
+            Note {
+                fields: [
+                    format!("{}_{i}", entry.id),
+                    entry.head.clone(),
+                    format!("<img src=\"{svg_name}\">"),
+                    sound,
+                ],
+                svg: (svg_name, gen_svg(&accent.accent)),
+                audio,
+            }
+        })
+        .collect()
+}
+
+fn models(deck_id: i64, secs: i64) -> serde_json::Value {
+    let fields: Vec<_> = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name, "ord": ord, "sticky": false, "rtl": false,
+                "font": "Arial", "size": 20, "media": [],
+            })
+        })
+        .collect();
+    json!({
+        MODEL_ID.to_string(): {
+            "id": MODEL_ID,
+            "name": MODEL_NAME,
+            "type": 0,
+            "mod": secs,
+            "usn": -1,
+            "sortf": 1,
+            "did": deck_id,
+            "tmpls": [{
+                "name": "Card 1",
+                "ord": 0,
+                "qfmt": "{{voc}}",
+                "afmt": "{{FrontSide}}<hr id=answer>{{pitch}}<br>{{audio}}",
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+            }],
+            "flds": fields,
+            "css": ".card { font-family: sans-serif; font-size: 24px; text-align: center; }",
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "latexsvg": false,
+            "req": [[0, "any", [1]]],
+            "tags": [],
+            "vers": [],
+        }
+    })
+}
+
+fn deck(id: i64, name: &str, secs: i64) -> serde_json::Value {
+    json!({
+        "id": id, "name": name, "mod": secs, "usn": -1, "desc": "", "dyn": 0,
+        "conf": 1, "collapsed": false, "browserCollapsed": false,
+        "extendNew": 0, "extendRev": 0,
+        "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0],
+    })
+}
+
+fn decks(deck_id: i64, name: &str, secs: i64) -> serde_json::Value {
+    json!({
+        DEFAULT_DECK_ID.to_string(): deck(DEFAULT_DECK_ID, "Default", secs),
+        deck_id.to_string(): deck(deck_id, name, secs),
+    })
+}
+
+fn conf(deck_id: i64) -> serde_json::Value {
+    json!({
+        "activeDecks": [deck_id], "curDeck": deck_id, "curModel": MODEL_ID,
+        "nextPos": 1, "estTimes": true, "sortType": "noteFld", "sortBackwards": false,
+        "timeLim": 0, "addToCur": true, "dueCounts": true, "collapseTime": 1200,
+    })
+}
+
+fn dconf() -> serde_json::Value {
+    json!({
+        "1": {
+            "id": 1, "name": "Default", "mod": 0, "usn": 0, "maxTaken": 60,
+            "autoplay": true, "timer": 0, "replayq": true, "dyn": false,
+            "new": {
+                "bury": true, "delays": [1, 10], "initialFactor": 2500,
+                "ints": [1, 4, 7], "order": 1, "perDay": 20, "separate": true,
+            },
+            "rev": {
+                "bury": true, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1,
+                "maxIvl": 36500, "minSpace": 1, "perDay": 100,
+            },
+            "lapse": {
+                "delays": [10], "leechAction": 0, "leechFails": 8, "minInt": 1, "mult": 0,
+            },
+        }
+    })
+}
+
+/// Deck ids derived from the name, so exporting a deck again updates it.
+fn deck_id(name: &str) -> i64 {
+    let digest = Sha1::from(name).digest().bytes();
+    let id = u64::from_be_bytes(digest[..8].try_into().unwrap_or_default());
+    // positive and well above the ids of decks made in Anki
+    (id >> 24) as i64 + (1 << 40)
+}
+
+/// Stable note guids, so reimporting a deck updates its notes instead of duplicating them.
+fn guid(dict: &str) -> String {
+    let mut guid = Sha1::from(format!("accent_dict:{dict}"))
+        .digest()
+        .to_string();
+    guid.truncate(16);
+    guid
+}
+
+/// Anki's duplicate check: the first 32 bits of the sha1 of the sort field.
+fn checksum(sort_field: &str) -> i64 {
+    let digest = Sha1::from(sort_field).digest().bytes();
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as i64
+}
+
+/// RFC 4648 base32 without padding, like the add-on's `sanitise_str`.
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut buf = [0; 8];
+        buf[3..3 + chunk.len()].copy_from_slice(chunk);
+        let bits = u64::from_be_bytes(buf);
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            out.push(ALPHABET[(bits >> (35 - 5 * i) & 31) as usize] as char);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Cursor, Read},
+    };
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::{
+        NrscFormat, NrscWriter,
+        fixture::{AME_SOUND, SAKURA_SOUND, build_dict, sound_data, temp_dir},
+    };
+
+    #[test]
+    fn base32_like_python() {
+        // base64.b32encode(...).rstrip('=')
+        assert_eq!(base32(b""), "");
+        assert_eq!(base32(b"f"), "MY");
+        assert_eq!(base32(b"fooba"), "MZXW6YTB");
+        assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32("サクラ▔".as_bytes()), "4OBLLY4CV7RYHKPCS2KA");
+    }
+
+    #[test]
+    fn deck_package() {
+        let dir = build_dict("anki");
+        let mut out = Cursor::new(Vec::new());
+        let writer = AnkiWriter::new("NHK::Weather");
+        let exported = writer
+            .write(dir.to_str().unwrap(), &mut out, |entry| {
+                entry.reading != "はなみ"
+            })
+            .unwrap();
+        // さくら, あめ, ―ほん and its counter form
+        assert_eq!(exported.count, 4);
+
+        let mut zip = ZipArchive::new(Cursor::new(out.into_inner())).unwrap();
+        let mut media = String::new();
+        zip.by_name("media")
+            .unwrap()
+            .read_to_string(&mut media)
+            .unwrap();
+        let media: HashMap<String, String> = serde_json::from_str(&media).unwrap();
+        // a diagram for every accent and three audio clips
+        assert_eq!(media.len(), 7);
+        let (n, _) = media
+            .iter()
+            .find(|(_, name)| **name == format!("{SAKURA_SOUND}.aac"))
+            .unwrap();
+        let mut clip = Vec::new();
+        zip.by_name(n).unwrap().read_to_end(&mut clip).unwrap();
+        assert_eq!(clip, sound_data(SAKURA_SOUND));
+
+        let db_dir = temp_dir("anki_db");
+        let db_path = db_dir.join("collection.anki2");
+        let mut collection = Vec::new();
+        let mut file = zip.by_name("collection.anki2").unwrap();
+        file.read_to_end(&mut collection).unwrap();
+        fs::write(&db_path, collection).unwrap();
+        let db = Connection::open(&db_path).unwrap();
+        let flds: String = db
+            .query_row("SELECT flds FROM notes ORDER BY id LIMIT 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let svg = base32("サクラ▔".as_bytes());
+        assert_eq!(
+            flds.split('\x1f').collect::<Vec<_>>(),
+            [
+                "00001_0",
+                "さくら 【桜】",
+                &format!("<img src=\"pitch_v2_{svg}.svg\">"),
+                &format!("[sound:{SAKURA_SOUND}.aac]"),
+            ]
+        );
+        let cards: i64 = db
+            .query_row(
+                "SELECT count(*) FROM cards WHERE did = ?1",
+                [deck_id("NHK::Weather")],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(cards, 4);
+        let decks: String = db
+            .query_row("SELECT decks FROM col", [], |row| row.get(0))
+            .unwrap();
+        assert!(decks.contains("NHK::Weather"));
+    }
+
+    #[test]
+    fn unreadable_audio() {
+        let dir = build_dict("anki_audio");
+        let audio = dir.join("audio");
+        fs::remove_dir_all(&audio).unwrap();
+        fs::create_dir(&audio).unwrap();
+        let mut writer = NrscWriter::new(&audio);
+        writer
+            .add(AME_SOUND, &sound_data(AME_SOUND), NrscFormat::Uncompressed)
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut out = Cursor::new(Vec::new());
+        let exported = AnkiWriter::new("NHK")
+            .write(dir.to_str().unwrap(), &mut out, |_| true)
+            .unwrap();
+        assert_eq!(exported.count, 5);
+
+        let mut zip = ZipArchive::new(Cursor::new(out.into_inner())).unwrap();
+        let mut media = String::new();
+        zip.by_name("media")
+            .unwrap()
+            .read_to_string(&mut media)
+            .unwrap();
+        let media: HashMap<String, String> = serde_json::from_str(&media).unwrap();
+        let clips: Vec<_> = media
+            .values()
+            .filter(|name| name.ends_with(".aac"))
+            .collect();
+        assert_eq!(clips, [&format!("{AME_SOUND}.aac")]);
+
+        let db_dir = temp_dir("anki_audio_db");
+        let db_path = db_dir.join("collection.anki2");
+        let mut collection = Vec::new();
+        let mut file = zip.by_name("collection.anki2").unwrap();
+        file.read_to_end(&mut collection).unwrap();
+        fs::write(&db_path, collection).unwrap();
+        let db = Connection::open(&db_path).unwrap();
+        let sounds: Vec<String> = db
+            .prepare("SELECT flds FROM notes ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .map(|flds| flds.unwrap().rsplit('\x1f').next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            sounds,
+            ["", &format!("[sound:{AME_SOUND}.aac]"), "", "", ""]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! CSV and TSV export for spreadsheets, one row per accent with a header row.
+
+use std::{collections::HashMap, fmt, io::Write, str::FromStr};
+
+use super::{AccentShape, Exported, for_each_entry, key_indexes};
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    /// Entry id and number of the accent, like the add-on's `dict` field.
+    Id,
+    Head,
+    Kanji,
+    Reading,
+    Accent,
+    Downstep,
+    Class,
+    Morae,
+    /// File name of the audio clip.
+    Audio,
+    /// Key indexes the page is found in, `headword`, `compound` or `counter`.
+    WordType,
+}
+
+impl Column {
+    pub const ALL: [Column; 10] = [
+        Column::Id,
+        Column::Head,
+        Column::Kanji,
+        Column::Reading,
+        Column::Accent,
+        Column::Downstep,
+        Column::Class,
+        Column::Morae,
+        Column::Audio,
+        Column::WordType,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Head => "head",
+            Column::Kanji => "kanji",
+            Column::Reading => "reading",
+            Column::Accent => "accent",
+            Column::Downstep => "downstep",
+            Column::Class => "class",
+            Column::Morae => "morae",
+            Column::Audio => "audio",
+            Column::WordType => "word_type",
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Column::ALL
+            .into_iter()
+            .find(|c| c.name() == s)
+            .ok_or(Error::InvalidArg)
+    }
+}
+
+/// Writes the accents of a dictionary as comma or tab separated values.
+#[derive(Debug)]
+pub struct CsvWriter {
+    columns: Vec<Column>,
+    delimiter: char,
+}
+
+impl CsvWriter {
+    /// Comma separated, with `columns` in the given order.
+    pub fn new(columns: &[Column]) -> Self {
+        CsvWriter {
+            columns: columns.to_vec(),
+            delimiter: ',',
+        }
+    }
+
+    /// Tab separated, with `columns` in the given order.
+    pub fn tsv(columns: &[Column]) -> Self {
+        CsvWriter {
+            columns: columns.to_vec(),
+            delimiter: '\t',
+        }
+    }
+
+    /// Writes the header and a row for every accent of the dictionary at `path`,
+    /// returns the number of rows.
+    pub fn write(&self, path: &str, mut out: impl Write) -> Result<Exported, Error> {
+        let word_types = if self.columns.contains(&Column::WordType) {
+            word_types(path)?
+        } else {
+            HashMap::new()
+        };
+
+        let header: Vec<&str> = self.columns.iter().map(|c| c.name()).collect();
+        self.write_row(&mut out, &header)?;
+        let mut count = 0;
+        let skipped = for_each_entry(path, |entry| {
+            for (i, accent) in entry.accents.iter().enumerate() {
+                let shape = AccentShape::new(&accent.accent);
+                let row: Vec<String> = self
+                    .columns
+                    .iter()
+                    .map(|column| match column {
+                        Column::Id => format!("{}_{i}", entry.id),
+                        Column::Head => entry.head.clone(),
+                        Column::Kanji => entry.kanji.clone().unwrap_or_default(),
+                        Column::Reading => entry.reading.clone(),
+                        Column::Accent => accent.accent.clone(),
+                        Column::Downstep => {
+                            shape.map(|s| s.downstep.to_string()).unwrap_or_default()
+                        }
+                        Column::Class => shape.map(|s| s.class.to_string()).unwrap_or_default(),
+                        Column::Morae => shape.map(|s| s.morae.to_string()).unwrap_or_default(),
+                        Column::Audio => accent
+                            .audio
+                            .as_ref()
+                            .map(|id| format!("{id}.aac"))
+                            .unwrap_or_default(),
+                        Column::WordType => word_types
+                            .get(&entry.page)
+                            .map(|types| types.join(" "))
+                            .unwrap_or_default(),
+                    })
+                    .collect();
+                self.write_row(&mut out, &row)?;
+                count += 1;
+            }
+            Ok(())
+        })?;
+        out.flush()?;
+        Ok(Exported { count, skipped })
+    }
+
+    fn write_row(&self, out: &mut impl Write, row: &[impl AsRef<str>]) -> Result<(), Error> {
+        let mut line = String::new();
+        for (i, field) in row.iter().enumerate() {
+            if i > 0 {
+                line.push(self.delimiter);
+            }
+            let field = field.as_ref();
+            if field.contains([self.delimiter, '"', '\n', '\r']) {
+                line.push('"');
+                line.push_str(&field.replace('"', "\"\""));
+                line.push('"');
+            } else {
+                line.push_str(field);
+            }
+        }
+        line.push('\n');
+        out.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Word types of every page, by the key indexes that lead to it.
+fn word_types(path: &str) -> Result<HashMap<u32, Vec<&'static str>>, Error> {
+    let mut word_types: HashMap<u32, Vec<&str>> = HashMap::new();
+    for (keys, word_type) in key_indexes(path)? {
+        for idx in 0..keys.index_prefix.len() {
+            let (_, pages) = keys.get_idx(&keys.index_prefix, idx)?;
+            for id in pages {
+                let types = word_types.entry(id.page).or_default();
+                if !types.contains(&word_type) {
+                    types.push(word_type);
+                }
+            }
+        }
+    }
+    Ok(word_types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dict::NUMERAL_KEYS,
+        fixture::{SAKURA_SOUND, build_dict},
+    };
+
+    #[test]
+    fn columns() {
+        assert_eq!("word_type".parse::<Column>(), Ok(Column::WordType));
+        assert_eq!("pitch".parse::<Column>(), Err(Error::InvalidArg));
+        for column in Column::ALL {
+            assert_eq!(column.name().parse(), Ok(column));
+        }
+    }
+
+    #[test]
+    fn csv_and_tsv() {
+        let dir = build_dict("csv");
+        let path = dir.to_str().unwrap();
+
+        let mut out = Vec::new();
+        let exported = CsvWriter::new(&Column::ALL).write(path, &mut out).unwrap();
+        assert_eq!(exported.count, 5);
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "id,head,kanji,reading,accent,downstep,class,morae,audio,word_type"
+        );
+        assert_eq!(
+            lines[1],
+            format!(
+                "00001_0,さくら 【桜】,桜,さくら,サクラ▔,0,heiban,3,{SAKURA_SOUND}.aac,headword"
+            )
+        );
+        assert_eq!(
+            lines[4],
+            "00003-0001_0,1,,1,イッ＼ポン,2,nakadaka,4,20180411131924.aac,counter"
+        );
+
+        let mut out = Vec::new();
+        CsvWriter::tsv(&[Column::Kanji, Column::Class, Column::WordType])
+            .write(path, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().nth(5), Some("花見\tatamadaka\tcompound"));
+
+        // a dictionary without numeral keys has no counter word type
+        std::fs::remove_file(dir.join("key").join(NUMERAL_KEYS)).unwrap();
+        let mut out = Vec::new();
+        CsvWriter::new(&[Column::Id, Column::WordType])
+            .write(path, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().nth(4), Some("00003-0001_0,"));
+    }
+
+    #[test]
+    fn quoting() {
+        let writer = CsvWriter::new(&[]);
+        let mut out = Vec::new();
+        writer
+            .write_row(&mut out, &["a,b", "say \"hi\"", "plain", "two\nlines"])
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\"a,b\",\"say \"\"hi\"\"\",plain,\"two\nlines\"\n"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! JSON Lines export, one `Entry` per line:
+//!
+//! ```json
+//! {"version":1,"page":1,"id":"00001","reading":"さくら","head":"さくら 【桜】","kanji":"桜","accents":[{"accent":"サクラ▔","audio":"20170630130152"}]}
+//! ```
+//!
+//! `kanji` and `audio` are `null` when missing. Fields are only added within a
+//! version, renaming or removing one increases `version`.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use super::{Entry, Exported, for_each_entry};
+use crate::Error;
+
+/// Version of the JSON representation of `Entry`.
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    version: u32,
+    #[serde(flatten)]
+    entry: &'a Entry,
+}
+
+/// Writes all entries of the dictionary at `path` as JSON Lines, returns how
+/// many were written.
+pub fn write_jsonl(path: &str, mut out: impl Write) -> Result<Exported, Error> {
+    let mut count = 0;
+    let skipped = for_each_entry(path, |entry| {
+        write_entry(&mut out, &entry)?;
+        count += 1;
+        Ok(())
+    })?;
+    out.flush()?;
+    Ok(Exported { count, skipped })
+}
+
+fn write_entry(out: &mut impl Write, entry: &Entry) -> Result<(), Error> {
+    let json = JsonEntry {
+        version: JSON_VERSION,
+        entry,
+    };
+    serde_json::to_writer(&mut *out, &json).map_err(|_| Error::IOError)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        RscWriter, SkippedPage,
+        fixture::{IPPON_SOUND, SAKURA_SOUND, build_dict, page_xml},
+    };
+
+    #[test]
+    fn jsonl() {
+        let dir = build_dict("export_jsonl");
+        let mut out = Vec::new();
+        let exported = write_jsonl(dir.to_str().unwrap(), &mut out).unwrap();
+        // four headwords and one counter form
+        assert_eq!(exported.count, 5);
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            format!(
+                r#"{{"version":1,"page":1,"id":"00001","reading":"さくら","head":"さくら 【桜】","kanji":"桜","accents":[{{"accent":"サクラ▔","audio":"{SAKURA_SOUND}"}}]}}"#
+            )
+        );
+
+        let ippon: serde_json::Value = serde_json::from_str(lines[3]).unwrap();
+        assert_eq!(ippon["page"], 3);
+        assert_eq!(ippon["id"], "00003-0001");
+        assert_eq!(ippon["kanji"], serde_json::Value::Null);
+        assert_eq!(ippon["accents"][0]["audio"], IPPON_SOUND);
+    }
+
+    #[test]
+    fn skipped_pages() {
+        let dir = build_dict("export_skipped");
+        let contents = dir.join("contents");
+        fs::remove_dir_all(&contents).unwrap();
+        fs::create_dir(&contents).unwrap();
+        let mut writer = RscWriter::new(&contents, "contents");
+        writer.add(1, page_xml(1, "さくら").as_bytes()).unwrap();
+        writer.add(2, b"<html>").unwrap();
+        writer.finish().unwrap();
+
+        let mut out = Vec::new();
+        let exported = write_jsonl(dir.to_str().unwrap(), &mut out).unwrap();
+        assert_eq!(exported.count, 1);
+        assert!(matches!(
+            exported.skipped[..],
+            [SkippedPage::Unparsable { page: 2, .. }]
+        ));
+    }
+}
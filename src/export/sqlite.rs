@@ -0,0 +1,250 @@
+//! SQLite database of the whole dictionary:
+//!
+//! - `entries`: one row per headword or counter form, with reading, headline and kanji.
+//! - `accents`: the accents of an entry, as printed and as downstep, mora count and
+//!   accent class, with the id of their audio clip.
+//! - `keys`: the words of the key indexes and the pages they lead to. The index a
+//!   word is in is its word type: `headword`, `compound` or `counter`.
+//! - `refs`: cross references of the pages, to other pages or to the appendix.
+
+use std::{fs, path::Path};
+
+use rusqlite::{Connection, params};
+
+use super::{AccentShape, Entry, Exported, for_each_page, key_indexes};
+use crate::{BodyContent, DicItem, Error, ExampleContent, Keys, RefContent, unpack_dic_item};
+
+const SCHEMA: &str = "
+CREATE TABLE entries (
+    id INTEGER PRIMARY KEY,
+    page INTEGER NOT NULL,
+    entry_id TEXT NOT NULL,
+    reading TEXT NOT NULL,
+    head TEXT NOT NULL,
+    kanji TEXT
+);
+CREATE TABLE accents (
+    entry INTEGER NOT NULL REFERENCES entries (id),
+    ord INTEGER NOT NULL,
+    accent TEXT NOT NULL,
+    -- morae before the drop, 0 for heiban, NULL for accents of several words
+    downstep INTEGER,
+    morae INTEGER,
+    -- heiban, atamadaka, nakadaka or odaka
+    class TEXT,
+    audio_id TEXT,
+    PRIMARY KEY (entry, ord)
+);
+CREATE TABLE keys (
+    word TEXT NOT NULL,
+    word_type TEXT NOT NULL,
+    page INTEGER NOT NULL,
+    item INTEGER NOT NULL
+);
+CREATE TABLE refs (
+    page INTEGER NOT NULL,
+    target TEXT NOT NULL,
+    text TEXT NOT NULL
+);
+CREATE INDEX entries_page ON entries (page);
+CREATE INDEX entries_reading ON entries (reading);
+CREATE INDEX entries_kanji ON entries (kanji);
+CREATE INDEX accents_audio_id ON accents (audio_id);
+CREATE INDEX keys_word ON keys (word);
+CREATE INDEX keys_page ON keys (page);
+CREATE INDEX refs_page ON refs (page);
+CREATE INDEX refs_target ON refs (target);
+";
+
+/// Writes the dictionary at `path` to a new SQLite database at `db_path`, an
+/// existing file is replaced. Returns the number of entries.
+pub fn write_sqlite(path: &str, db_path: &Path) -> Result<Exported, Error> {
+    if db_path.exists() {
+        fs::remove_file(db_path)?;
+    }
+    let mut db = Connection::open(db_path)?;
+    db.execute_batch(SCHEMA)?;
+    let tx = db.transaction()?;
+
+    let mut count = 0;
+    let skipped = for_each_page(path, |page, item| {
+        insert_refs(&tx, page, &item)?;
+        for unpacked in unpack_dic_item(item) {
+            insert_entry(&tx, &Entry::new(page, unpacked))?;
+            count += 1;
+        }
+        Ok(())
+    })?;
+    for (keys, word_type) in key_indexes(path)? {
+        insert_keys(&tx, &keys, word_type)?;
+    }
+    tx.commit()?;
+    Ok(Exported { count, skipped })
+}
+
+fn insert_entry(db: &Connection, entry: &Entry) -> Result<(), Error> {
+    db.execute(
+        "INSERT INTO entries (page, entry_id, reading, head, kanji) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![entry.page, entry.id, entry.reading, entry.head, entry.kanji],
+    )?;
+    let id = db.last_insert_rowid();
+    for (ord, accent) in entry.accents.iter().enumerate() {
+        let shape = AccentShape::new(&accent.accent);
+        let downstep = shape.map(|s| s.downstep);
+        let morae = shape.map(|s| s.morae);
+        let class = shape.map(|s| s.class);
+        db.execute(
+            "INSERT INTO accents VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![id, ord, accent.accent, downstep, morae, class, accent.audio],
+        )?;
+    }
+    Ok(())
+}
+
+fn insert_refs(db: &Connection, page: u32, item: &DicItem) -> Result<(), Error> {
+    let body = item.1.iter().flat_map(|head_g| &head_g.1.0);
+    for content in body {
+        let refs = match content {
+            BodyContent::Ref(target, text) => vec![(target, text)],
+            BodyContent::Example(example) => example
+                .1
+                .iter()
+                .filter_map(|content| match content {
+                    ExampleContent::Ref(target, text) => Some((target, text)),
+                    _ => None,
+                })
+                .collect(),
+            _ => continue,
+        };
+        for (target, text) in refs {
+            let text: String = text
+                .iter()
+                .map(|t| match t {
+                    RefContent::Text(s)
+                    | RefContent::RoundBrackets(s)
+                    | RefContent::TextSpan(s) => s.as_str(),
+                })
+                .collect();
+            db.execute(
+                "INSERT INTO refs VALUES (?1, ?2, ?3)",
+                params![page, target.0, text],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_keys(db: &Connection, keys: &Keys, word_type: &str) -> Result<(), Error> {
+    let mut insert = db.prepare("INSERT INTO keys VALUES (?1, ?2, ?3, ?4)")?;
+    for idx in 0..keys.index_prefix.len() {
+        let (word, pages) = keys.get_idx(&keys.index_prefix, idx)?;
+        for id in pages {
+            insert.execute(params![word, word_type, id.page, id.item])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RscWriter,
+        fixture::{AME_SOUND, build_dict, page_xml, temp_dir},
+    };
+
+    #[test]
+    fn database() {
+        let dir = build_dict("sqlite");
+        // a cross reference from 花見 to 桜
+        let contents = dir.join("contents");
+        fs::remove_dir_all(&contents).unwrap();
+        fs::create_dir(&contents).unwrap();
+        let mut writer = RscWriter::new(&contents, "contents");
+        for (id, headword) in [(1, "さくら"), (2, "あめ"), (3, "―ほん")] {
+            writer.add(id, page_xml(id, headword).as_bytes()).unwrap();
+        }
+        let page = page_xml(4, "はなみ").replace(
+            "</span></div></span></span></body>",
+            "</span><span class=\"ref\"><a href=\"00001\"><text>☞</text>さくら</a></span></div></span></span></body>",
+        );
+        writer.add(4, page.as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let db_dir = temp_dir("sqlite_db");
+        let db_path = db_dir.join("nhk.sqlite");
+        fs::write(&db_path, b"replaced").unwrap();
+        let exported = write_sqlite(dir.to_str().unwrap(), &db_path).unwrap();
+        assert_eq!(exported.count, 5);
+
+        let db = Connection::open(&db_path).unwrap();
+        let row: (
+            String,
+            Option<String>,
+            String,
+            i64,
+            i64,
+            String,
+            Option<String>,
+        ) = db
+            .query_row(
+                "SELECT reading, kanji, accent, downstep, morae, class, audio_id
+                 FROM entries JOIN accents ON accents.entry = entries.id
+                 WHERE reading = 'あめ'",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                "あめ".to_string(),
+                Some("雨".to_string()),
+                "ア＼メ".to_string(),
+                1,
+                2,
+                "atamadaka".to_string(),
+                Some(AME_SOUND.to_string())
+            )
+        );
+
+        let classes: Vec<String> = db
+            .prepare("SELECT class FROM accents JOIN entries ON accents.entry = entries.id ORDER BY page, entries.id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            classes,
+            ["heiban", "atamadaka", "heiban", "nakadaka", "atamadaka"]
+        );
+
+        let word_type: String = db
+            .query_row(
+                "SELECT word_type FROM keys JOIN entries ON keys.page = entries.page
+                 WHERE entries.kanji = '花見'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(word_type, "compound");
+
+        let reference: (u32, String, String) = db
+            .query_row("SELECT * FROM refs", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(reference, (4, "00001".to_string(), "☞さくら".to_string()));
+    }
+}
@@ -0,0 +1,366 @@
+//! Yomitan (and Yomichan) pitch accent dictionary: a zip with `index.json` and
+//! `term_meta_bank_N.json` files of `pitch` term meta entries.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use super::{Entry, Exported, for_each_entry};
+use crate::{
+    Error, MonokakidoDict,
+    pitch::{Mora, parse_pitch},
+};
+
+const DEFAULT_BANK_SIZE: usize = 10_000;
+
+/// Builds a Yomitan dictionary from the dictionary at a path.
+#[derive(Debug)]
+pub struct YomitanWriter {
+    title: String,
+    revision: String,
+    audio_dir: Option<PathBuf>,
+    bank_size: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Index<'a> {
+    title: &'a str,
+    revision: &'a str,
+    format: u32,
+    sequenced: bool,
+    source_language: &'a str,
+    target_language: &'a str,
+    description: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Pitch {
+    position: Position,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nasal: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    devoice: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+enum Position {
+    /// Morae before the drop, 0 for heiban.
+    Downstep(usize),
+    /// `H` or `L` for every mora, for accents of several words.
+    Pattern(String),
+}
+
+#[derive(Serialize)]
+struct PitchData<'a> {
+    reading: &'a str,
+    pitches: &'a [Pitch],
+}
+
+impl YomitanWriter {
+    pub fn new(title: &str) -> Self {
+        YomitanWriter {
+            title: title.to_string(),
+            revision: "1".to_string(),
+            audio_dir: None,
+            bank_size: DEFAULT_BANK_SIZE,
+        }
+    }
+
+    /// Revision in `index.json`, Yomitan uses it to tell updates apart.
+    pub fn set_revision(&mut self, revision: &str) {
+        self.revision = revision.to_string();
+    }
+
+    /// Also writes the audio clips to `dir`, as `<reading>/<term>.aac`, for use
+    /// as a local audio source. Further clips of a term get a `_2`, `_3`, ... suffix.
+    pub fn set_audio_dir(&mut self, dir: &Path) {
+        self.audio_dir = Some(dir.to_path_buf());
+    }
+
+    /// Number of entries per `term_meta_bank_N.json`.
+    pub fn set_bank_size(&mut self, entries: usize) {
+        self.bank_size = entries.max(1);
+    }
+
+    /// Writes the zip for the dictionary at `path` to `out`, returns the number of
+    /// term meta entries.
+    pub fn write(&self, path: &str, out: impl Write + Seek) -> Result<Exported, Error> {
+        // (term, reading) -> pitches
+        let mut terms: BTreeMap<(String, String), Vec<Pitch>> = BTreeMap::new();
+        // (reading, term, audio id)
+        let mut clips = Vec::new();
+        let skipped = for_each_entry(path, |entry| {
+            for (term, reading, pitch, audio) in pitches(&entry) {
+                let pitches = terms.entry((term.clone(), reading.clone())).or_default();
+                if !pitches.contains(&pitch) {
+                    pitches.push(pitch);
+                }
+                if let Some(audio) = audio {
+                    clips.push((reading, term, audio));
+                }
+            }
+            Ok(())
+        })?;
+
+        let mut zip = ZipWriter::new(out);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let index = Index {
+            title: &self.title,
+            revision: &self.revision,
+            format: 3,
+            sequenced: false,
+            source_language: "ja",
+            target_language: "ja",
+            description: "Pitch accents of the NHK accent dictionary",
+        };
+        zip.start_file("index.json", options)?;
+        zip.write_all(&to_json(&index)?)?;
+
+        let metas: Vec<_> = terms
+            .iter()
+            .map(|((term, reading), pitches)| (term, "pitch", PitchData { reading, pitches }))
+            .collect();
+        for (n, bank) in metas.chunks(self.bank_size).enumerate() {
+            zip.start_file(format!("term_meta_bank_{}.json", n + 1), options)?;
+            zip.write_all(&to_json(&bank)?)?;
+        }
+        zip.finish()?;
+
+        if let Some(dir) = &self.audio_dir {
+            write_audio(path, dir, &clips)?;
+        }
+        Ok(Exported {
+            count: metas.len(),
+            skipped,
+        })
+    }
+}
+
+/// Term, reading, pitch and audio id of every accent of `entry` with a reading.
+/// The reading is the kana headword, as in term dictionaries, the accent spells
+/// long vowels with `ー`.
+fn pitches(entry: &Entry) -> Vec<(String, String, Pitch, Option<String>)> {
+    let mut pitches = Vec::new();
+    for accent in &entry.accents {
+        let words = parse_pitch(&accent.accent);
+        let morae: Vec<_> = words.iter().flat_map(|w| &w.morae).collect();
+        if morae.is_empty() {
+            continue;
+        }
+        let reading = if entry.reading.chars().all(is_kana) {
+            entry.reading.clone()
+        } else {
+            // counter forms are listed under a number
+            morae.iter().map(|m| m.kana()).collect()
+        };
+        let position = match &words[..] {
+            [word] => Position::Downstep(word.downstep.unwrap_or(0)),
+            _ => Position::Pattern(
+                morae
+                    .iter()
+                    .map(|m| if m.high { 'H' } else { 'L' })
+                    .collect(),
+            ),
+        };
+        // counted from 1
+        let positions = |f: fn(&Mora) -> bool| -> Vec<usize> {
+            let morae = morae.iter().enumerate();
+            morae.filter(|(_, m)| f(m)).map(|(i, _)| i + 1).collect()
+        };
+        let pitch = Pitch {
+            position,
+            nasal: positions(|m| m.nasal),
+            devoice: positions(|m| m.devoiced),
+        };
+        let term = entry.kanji.clone().unwrap_or_else(|| reading.clone());
+        pitches.push((term, reading, pitch, accent.audio.clone()));
+    }
+    pitches
+}
+
+fn is_kana(c: char) -> bool {
+    matches!(c, 'ぁ'..='ゖ' | 'ァ'..='ヺ' | 'ー')
+}
+
+/// Writes the clips to `dir`, leaving out those that can't be read.
+fn write_audio(path: &str, dir: &Path, clips: &[(String, String, String)]) -> Result<(), Error> {
+    let audio = MonokakidoDict::open_with_path(path)?.audio;
+    let mut written: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+    for (reading, term, id) in clips {
+        let ids = written.entry((reading, term)).or_default();
+        if ids.contains(&id.as_str()) {
+            continue;
+        }
+        let Ok(clip) = audio.get(id) else { continue };
+        ids.push(id);
+        let name = match ids.len() {
+            1 => format!("{term}.aac"),
+            n => format!("{term}_{n}.aac"),
+        };
+        let reading_dir = dir.join(reading);
+        fs::create_dir_all(&reading_dir)?;
+        fs::write(reading_dir.join(name), clip)?;
+    }
+    Ok(())
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>, Error> {
+    serde_json::to_vec(value).map_err(|_| Error::IOError)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use serde_json::{Value, json};
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::{
+        EntryAccent, NrscFormat, NrscWriter,
+        fixture::{IPPON_SOUND, SAKURA_SOUND, build_dict, sound_data, temp_dir},
+    };
+
+    fn read_json(zip: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Value {
+        let mut json = String::new();
+        zip.by_name(name)
+            .unwrap()
+            .read_to_string(&mut json)
+            .unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn pitch_dictionary() {
+        let dir = build_dict("yomitan");
+        let audio_dir = temp_dir("yomitan_audio");
+        let mut writer = YomitanWriter::new("NHK");
+        writer.set_revision("2016");
+        writer.set_bank_size(3);
+        writer.set_audio_dir(&audio_dir);
+        let mut out = Cursor::new(Vec::new());
+        let exported = writer.write(dir.to_str().unwrap(), &mut out).unwrap();
+        assert_eq!(exported.count, 5);
+
+        let mut zip = ZipArchive::new(Cursor::new(out.into_inner())).unwrap();
+        assert_eq!(zip.len(), 3);
+        let index = read_json(&mut zip, "index.json");
+        assert_eq!(index["title"], "NHK");
+        assert_eq!(index["revision"], "2016");
+        assert_eq!(index["format"], 3);
+
+        let mut metas = read_json(&mut zip, "term_meta_bank_1.json");
+        let bank_2 = read_json(&mut zip, "term_meta_bank_2.json");
+        metas
+            .as_array_mut()
+            .unwrap()
+            .extend_from_slice(bank_2.as_array().unwrap());
+        assert_eq!(
+            metas,
+            json!([
+                ["―本", "pitch", {"reading": "ほん", "pitches": [{"position": 0}]}],
+                ["いっぽん", "pitch", {"reading": "いっぽん", "pitches": [{"position": 2}]}],
+                ["桜", "pitch", {"reading": "さくら", "pitches": [{"position": 0}]}],
+                ["花見", "pitch", {"reading": "はなみ", "pitches": [{"position": 1}]}],
+                ["雨", "pitch", {"reading": "あめ", "pitches": [{"position": 1}]}],
+            ])
+        );
+
+        let clip = fs::read(audio_dir.join("さくら").join("桜.aac")).unwrap();
+        assert_eq!(clip, sound_data(SAKURA_SOUND));
+        let clip = fs::read(audio_dir.join("いっぽん").join("いっぽん.aac")).unwrap();
+        assert_eq!(clip, sound_data(IPPON_SOUND));
+        assert!(!audio_dir.join("はなみ").exists());
+    }
+
+    #[test]
+    fn unreadable_audio() {
+        let dir = build_dict("yomitan_unreadable");
+        let audio = dir.join("audio");
+        fs::remove_dir_all(&audio).unwrap();
+        fs::create_dir(&audio).unwrap();
+        let mut writer = NrscWriter::new(&audio);
+        writer
+            .add(IPPON_SOUND, &sound_data(IPPON_SOUND), NrscFormat::Zlib)
+            .unwrap();
+        writer.finish().unwrap();
+
+        let audio_dir = temp_dir("yomitan_unreadable_audio");
+        let mut writer = YomitanWriter::new("NHK");
+        writer.set_audio_dir(&audio_dir);
+        let exported = writer
+            .write(dir.to_str().unwrap(), Cursor::new(Vec::new()))
+            .unwrap();
+        assert_eq!(exported.count, 5);
+        assert!(audio_dir.join("いっぽん").join("いっぽん.aac").exists());
+        assert!(!audio_dir.join("さくら").exists());
+        assert!(!audio_dir.join("あめ").exists());
+    }
+
+    #[test]
+    fn nasal_devoiced_and_compound() {
+        let entry = Entry {
+            page: 1,
+            id: "00001".to_string(),
+            reading: "じゅうごにち".to_string(),
+            head: "じゅうごにち【十五日】".to_string(),
+            kanji: Some("十五日".to_string()),
+            accents: vec![EntryAccent {
+                accent: "ジュ＼ー・ゴ＼ニチ".to_string(),
+                audio: None,
+            }],
+        };
+        let headword = pitches(&entry);
+        assert_eq!(headword[0].0, "十五日");
+        assert_eq!(headword[0].1, "じゅうごにち");
+        assert_eq!(
+            headword[0].2.position,
+            Position::Pattern("HLHLL".to_string())
+        );
+
+        let entry = Entry {
+            reading: "1".to_string(),
+            head: "1".to_string(),
+            kanji: None,
+            accents: vec![EntryAccent {
+                accent: "㋖ク・カ゚ワ▔".to_string(),
+                audio: None,
+            }],
+            ..entry
+        };
+        let counter = pitches(&entry);
+        assert_eq!(counter[0].0, "きくがわ");
+        assert_eq!(counter[0].1, "きくがわ");
+        assert_eq!(counter[0].2.devoice, [1]);
+        assert_eq!(counter[0].2.nasal, [3]);
+    }
+
+    #[test]
+    fn long_vowel_reading() {
+        let entry = Entry {
+            page: 1,
+            id: "00001".to_string(),
+            reading: "とうきょう".to_string(),
+            head: "とうきょう【東京】".to_string(),
+            kanji: Some("東京".to_string()),
+            accents: vec![EntryAccent {
+                accent: "トーキョー▔".to_string(),
+                audio: Some(SAKURA_SOUND.to_string()),
+            }],
+        };
+        let [(term, reading, pitch, audio)] = &pitches(&entry)[..] else {
+            panic!("one accent");
+        };
+        assert_eq!((term.as_str(), reading.as_str()), ("東京", "とうきょう"));
+        assert_eq!(pitch.position, Position::Downstep(0));
+        assert_eq!(audio.as_deref(), Some(SAKURA_SOUND));
+    }
+}
@@ -0,0 +1,219 @@
+//! A small made up dictionary, written with the format writers, so tests don't
+//! need the purchased NHK assets.
+
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::{KeysWriter, NrscFormat, NrscWriter, PageItemId, RscWriter};
+
+pub(crate) const SAKURA_SOUND: &str = "20170630130152";
+pub(crate) const AME_SOUND: &str = "20170630141404";
+pub(crate) const IPPON_SOUND: &str = "20180411131924";
+
+const XML_DECL: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+const HEAD: &str =
+    "<head><meta http-equiv=\"Content-Type\" content=\"text/html;charset=utf-8\"/></head>";
+
+/// Page id, entry XML and the key it is found under in the headword, compound
+/// or numeral index.
+const PAGES: [(u32, &str, Key); 4] = [
+    (1, "さくら", Key::Headword("サクラ")),
+    (2, "あめ", Key::Headword("アメ")),
+    (3, "―ほん", Key::Numeral("イッポン")),
+    (4, "はなみ", Key::Compound("ハナミ")),
+];
+
+#[derive(Clone, Copy)]
+enum Key {
+    Headword(&'static str),
+    Compound(&'static str),
+    Numeral(&'static str),
+}
+
+fn sound(id: &str) -> String {
+    format!(
+        "<span class=\"sound\"><a href=\"{id}.aac\"><img alt=\"音声\" src=\"HMDicAudio.png\"/></a></span>"
+    )
+}
+
+fn head_g(headword: &str, kanji: &str, accent: &str) -> String {
+    format!(
+        "<span class=\"head-g\"><div class=\"head\"><span class=\"h\"><span class=\"headword\">{headword}</span><span class=\"hw\">【{kanji}】</span></span></div><div class=\"body\"><span class=\"accent\"><span class=\"accent_text\">{accent}</span></span></div></span>"
+    )
+}
+
+pub(crate) fn page_xml(id: u32, headword: &str) -> String {
+    let macron = "<span class=\"symbol_macron\">▔</span>";
+    let drop = "<span class=\"symbol_backslash\">＼</span>";
+    let items = match id {
+        1 => head_g(
+            headword,
+            "桜",
+            &format!("サクラ{macron}{}", sound(SAKURA_SOUND)),
+        ),
+        2 => head_g(headword, "雨", &format!("ア{drop}メ{}", sound(AME_SOUND))),
+        3 => format!(
+            "{}<div class=\"josushi\"><span class=\"subheadword josushi\"><a name=\"00003-0001\" class=\"anchor\">1</a></span><span class=\"accent\"><span class=\"accent_text\">イッ{drop}ポン{}</span></span></div>",
+            head_g(headword, "―本", &format!("ホン{macron}")),
+            sound(IPPON_SOUND)
+        ),
+        _ => head_g(headword, "花見", &format!("ハ{drop}ナミ")),
+    };
+    format!(
+        "{XML_DECL}<html>{HEAD}<body><span class=\"dic-item\" id=\"{id:05}\">{items}</span></body></html>\n"
+    )
+}
+
+/// The audio clips are not real AAC, only their bytes are compared.
+pub(crate) fn sound_data(id: &str) -> Vec<u8> {
+    id.bytes().rev().collect()
+}
+
+/// A directory below the temp dir that is removed when the guard is dropped,
+/// also when the test fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A fresh, empty directory below the temp dir.
+pub(crate) fn temp_dir(name: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!("accent_dict_{name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+/// Writes the dictionary to a fresh directory below the temp dir.
+pub(crate) fn build_dict(name: &str) -> TempDir {
+    let dir = temp_dir(&format!("fixture_{name}"));
+    write_dict(&dir);
+    dir
+}
+
+/// The files of the dictionary with their paths in it, like `key/headword.keyindex`.
+pub(crate) fn dict_files(name: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = build_dict(name);
+    let mut files = Vec::new();
+    read_tree(&dir, &dir, &mut files);
+    files
+}
+
+fn read_tree(root: &Path, dir: &Path, files: &mut Vec<(String, Vec<u8>)>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            read_tree(root, &path, files);
+        } else {
+            let name = path.strip_prefix(root).unwrap().to_str().unwrap();
+            files.push((name.replace('\\', "/"), fs::read(&path).unwrap()));
+        }
+    }
+}
+
+fn write_dict(dir: &Path) {
+    let contents = dir.join("contents");
+    fs::create_dir_all(&contents).unwrap();
+    let mut writer = RscWriter::new(&contents, "contents");
+    writer.set_block_size(256);
+    for (id, headword, _) in PAGES {
+        writer.add(id, page_xml(id, headword).as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let audio = dir.join("audio");
+    fs::create_dir_all(&audio).unwrap();
+    let mut writer = NrscWriter::new(&audio);
+    writer
+        .add(SAKURA_SOUND, &sound_data(SAKURA_SOUND), NrscFormat::Zlib)
+        .unwrap();
+    writer
+        .add(AME_SOUND, &sound_data(AME_SOUND), NrscFormat::Uncompressed)
+        .unwrap();
+    writer
+        .add(IPPON_SOUND, &sound_data(IPPON_SOUND), NrscFormat::Zlib)
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut headword = KeysWriter::new();
+    let mut compound = KeysWriter::new();
+    let mut numeral = KeysWriter::new();
+    for (page, _, key) in PAGES {
+        let page = [PageItemId { page, item: 0 }];
+        match key {
+            Key::Headword(word) => headword.add(word, page),
+            Key::Compound(word) => compound.add(word, page),
+            Key::Numeral(word) => numeral.add(word, page),
+        }
+    }
+    let path = dir.to_str().unwrap();
+    headword.write(path, "headword.keyindex").unwrap();
+    compound.write(path, "compound.keyindex").unwrap();
+    numeral.write(path, "numeral.keyindex").unwrap();
+
+    write_headlines(dir);
+}
+
+/// `headline/headline.store` with one record per page. There is no writer for
+/// it. The header gives the offsets of the records and of the UTF-16 words,
+/// and each record has the offset of its word in the word area. Padding after
+/// the header keeps `rec_offset` apart from the header size. No store of the
+/// NHK assets was at hand to compare this layout with.
+fn write_headlines(dir: &Path) {
+    const HEADER_LEN: u32 = 32;
+    const PADDING: u32 = 16;
+    const REC_LEN: u32 = 0x18;
+    let mut words = Vec::new();
+    let mut recs = Vec::new();
+    for (page, headword, _) in PAGES {
+        recs.extend_from_slice(&page.to_le_bytes());
+        // item id, item type, unknown
+        recs.extend_from_slice(&[0, 0, 0, 0]);
+        recs.extend_from_slice(&(words.len() as u32).to_le_bytes());
+        recs.extend_from_slice(&[0; 12]);
+        words.extend(headword.encode_utf16().flat_map(u16::to_le_bytes));
+        words.extend_from_slice(&[0, 0]);
+    }
+    let len = PAGES.len() as u32;
+    let rec_offset = HEADER_LEN + PADDING;
+    let mut out = Vec::new();
+    for field in [
+        0,
+        2,
+        len,
+        rec_offset,
+        rec_offset + len * REC_LEN,
+        REC_LEN,
+        0,
+        0,
+    ] {
+        out.extend_from_slice(&u32::to_le_bytes(field));
+    }
+    out.extend_from_slice(&[0xff; PADDING as usize]);
+    out.append(&mut recs);
+    out.append(&mut words);
+
+    let headline = dir.join("headline");
+    fs::create_dir_all(&headline).unwrap();
+    fs::write(headline.join("headline.store"), out).unwrap();
+}
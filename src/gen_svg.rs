@@ -3,15 +3,14 @@ use svg::{
     node::element::{Circle, Path, Text, path::Data},
 };
 
-const CIRCLE: char = '\u{20dd}';
-const VOICED: char = '\u{309a}';
-const HALF_WIDTH_DAKUTEN: char = 'ﾞ';
-const HALF_WIDTH_HANDAKUTEN: char = 'ﾟ';
-const NAKATEN: char = '・';
+pub(crate) const CIRCLE: char = '\u{20dd}';
+pub(crate) const VOICED: char = '\u{309a}';
+pub(crate) const HALF_WIDTH_DAKUTEN: char = 'ﾞ';
+pub(crate) const HALF_WIDTH_HANDAKUTEN: char = 'ﾟ';
+pub(crate) const NAKATEN: char = '・';
 const TEXT_STYLE: &str = "font-size:25px;font-family:sans-serif;fill:#fff;stroke:#000;stroke-width:2.2px;paint-order:stroke;";
 
 pub fn gen_svg(accent_word: &str) -> String {
-    dbg!(accent_word);
     let mut doc = Document::new();
     let mora = str_to_mora(accent_word.trim());
     let mora_len = mora.len();
@@ -27,7 +26,6 @@ pub fn gen_svg(accent_word: &str) -> String {
         .enumerate()
     {
         let x = 16 + (pos * 35);
-        println!("draw");
         doc = draw_mora(doc, m, x.saturating_sub(11))
     }
 
@@ -49,7 +47,6 @@ pub fn gen_svg(accent_word: &str) -> String {
         start_x += (word.len()) * 35;
     }
 
-    println!("{}", doc);
     doc.to_string()
 }
 
@@ -244,7 +241,7 @@ fn split_words(mora: Vec<String>) -> Vec<Vec<String>> {
         .collect()
 }
 
-fn str_to_mora(word: &str) -> Vec<String> {
+pub(crate) fn str_to_mora(word: &str) -> Vec<String> {
     let little = "ぁぅぇぉゃゅょァゥェォャュョ";
     let mut mora: Vec<String> = Vec::new();
     for c in word.chars() {
@@ -1,12 +1,10 @@
-use std::{
-    fs::File,
-    io::{Read, Seek},
-    path::PathBuf,
-};
+use std::path::Path;
 
 use crate::{
-    Error, PageItemId,
+    Error, LoadMode, PageItemId,
     abi_utils::{LE32, TransmuteSafe, read_vec},
+    info::open_content,
+    storage::Storage,
 };
 
 mod abi {
@@ -65,20 +63,24 @@ pub struct Headlines {
 
 impl Headlines {
     pub fn new(path: &str) -> Result<Headlines, Error> {
-        let mut file = File::open(PathBuf::from(path).join("headline/").join("headline.store"))?;
-        let file_size = file.metadata()?.len() as usize;
+        Self::with_storage(&*open_content(Path::new(path), LoadMode::default())?)
+    }
+
+    pub fn with_storage(storage: &dyn Storage) -> Result<Headlines, Error> {
+        let file = storage.open("headline/headline.store")?;
+        let file = &*file;
+        let file_size = file.len() as usize;
         let mut hdr = FileHeader::default();
-        file.read_exact(hdr.as_bytes_mut())?;
+        file.read_exact_at(hdr.as_bytes_mut(), 0)?;
         hdr.validate()?;
 
-        file.seek(std::io::SeekFrom::Start(hdr.words_offset.read() as u64))?;
         let offsets: Option<Vec<Offset>> =
-            read_vec(&mut file, hdr.rec_offset.us(), hdr.words_offset.us())?;
+            read_vec(file, 0, hdr.rec_offset.us(), hdr.words_offset.us())?;
         let Some(recs) = offsets else {
             return Err(Error::InvalidIndex);
         };
 
-        let words: Option<Vec<u8>> = read_vec(&mut file, hdr.words_offset.us(), file_size)?;
+        let words: Option<Vec<u8>> = read_vec(file, 0, hdr.words_offset.us(), file_size)?;
         let Some(words) = words else {
             return Err(Error::InvalidIndex);
         };
@@ -99,3 +101,29 @@ impl Headlines {
         todo!();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::build_dict;
+
+    #[test]
+    fn records_and_words() {
+        let dir = build_dict("headline_layout");
+        let headlines = Headlines::new(dir.to_str().unwrap()).unwrap();
+        let pages: Vec<u32> = headlines.recs.iter().map(|r| r.page_id.read()).collect();
+        assert_eq!(pages, [1, 2, 3, 4]);
+        let words: Vec<String> = headlines
+            .recs
+            .iter()
+            .map(|rec| {
+                let units = headlines.words[rec.offset.us()..]
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .take_while(|&u| u != 0);
+                char::decode_utf16(units).map(Result::unwrap).collect()
+            })
+            .collect();
+        assert_eq!(words, ["さくら", "あめ", "―ほん", "はなみ"]);
+    }
+}
@@ -0,0 +1,122 @@
+use std::f32::consts::TAU;
+
+use crate::pitch::{Mora, parse_pitch};
+
+const SAMPLE_RATE: u32 = 22050;
+const MORA_SECONDS: f32 = 0.16;
+const WORD_GAP_SECONDS: f32 = 0.08;
+const FADE_SECONDS: f32 = 0.01;
+const LOW_HZ: f32 = 180.0;
+// a fifth above the low tone
+const HIGH_HZ: f32 = 270.0;
+const VOLUME: f32 = 0.5;
+const WHISPER_VOLUME: f32 = 0.08;
+
+/// Generates a short tone sequence following the accent pattern, one note per
+/// mora. Devoiced morae are whispered. Returns a 16 bit mono WAV file.
+pub fn gen_hum_wav(accent: &str) -> Vec<u8> {
+    let mora_len = (SAMPLE_RATE as f32 * MORA_SECONDS) as usize;
+    let gap_len = (SAMPLE_RATE as f32 * WORD_GAP_SECONDS) as usize;
+
+    let mut samples = Vec::new();
+    let mut phase = 0.0;
+    // fixed seed, the same accent always results in the same file
+    let mut noise = Lcg(0x2545_f491);
+    for (i, word) in parse_pitch(accent).iter().enumerate() {
+        if i > 0 {
+            samples.resize(samples.len() + gap_len, 0.0);
+        }
+        for mora in &word.morae {
+            let start = samples.len();
+            hum_mora(mora, mora_len, &mut phase, &mut noise, &mut samples);
+            fade(&mut samples[start..]);
+        }
+    }
+    to_wav(&samples)
+}
+
+fn hum_mora(mora: &Mora, len: usize, phase: &mut f32, noise: &mut Lcg, out: &mut Vec<f32>) {
+    if mora.devoiced {
+        out.extend((0..len).map(|_| noise.next() * WHISPER_VOLUME));
+        return;
+    }
+    let hz = if mora.high { HIGH_HZ } else { LOW_HZ };
+    let step = TAU * hz / SAMPLE_RATE as f32;
+    for _ in 0..len {
+        out.push(phase.sin() * VOLUME);
+        *phase = (*phase + step) % TAU;
+    }
+}
+
+/// Short linear fade in and out, so consecutive notes don't click.
+fn fade(samples: &mut [f32]) {
+    let fade_len = ((SAMPLE_RATE as f32 * FADE_SECONDS) as usize).min(samples.len() / 2);
+    let len = samples.len();
+    for i in 0..fade_len {
+        let gain = i as f32 / fade_len as f32;
+        samples[i] *= gain;
+        samples[len - 1 - i] *= gain;
+    }
+}
+
+fn to_wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16_u32.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1_u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2_u16.to_le_bytes());
+    wav.extend_from_slice(&16_u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for s in samples {
+        let s = (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&s.to_le_bytes());
+    }
+    wav
+}
+
+struct Lcg(u32);
+
+impl Lcg {
+    /// Uniform noise in -1.0..1.0
+    fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (self.0 >> 8) as f32 / (1 << 23) as f32 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_len(wav: &[u8]) -> usize {
+        u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn header() {
+        let wav = gen_hum_wav("サクラ▔");
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(wav.len(), 44 + data_len(&wav));
+    }
+
+    #[test]
+    fn one_note_per_mora() {
+        let mora_len = (SAMPLE_RATE as f32 * MORA_SECONDS) as usize;
+        assert_eq!(data_len(&gen_hum_wav("ア＼メ")), 2 * mora_len * 2);
+        assert_eq!(data_len(&gen_hum_wav("")), 0);
+    }
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(gen_hum_wav("㋖ク＼"), gen_hum_wav("㋖ク＼"));
+    }
+}
@@ -0,0 +1,255 @@
+//! What a dictionary is and which parts it has, from the metadata of the
+//! Monokakido product it comes from.
+//!
+//! A product directory, `jp.monokakido.Dictionaries.NHKACCENT2` for example,
+//! has a JSON file in `Contents/` that names the content directory next to it.
+//! The content directory holds `contents/`, `key/`, `audio/` and so on. The
+//! add-on's `assets/` folder is such a content directory without the product
+//! around it.
+
+use std::{path::Path, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::{
+    Error, LoadMode,
+    storage::{Storage, SubDir, join, open_path},
+};
+
+const PRODUCT_DIR: &str = "Contents";
+const NHK_ACCENT: &str = "NHKACCENT";
+
+/// Which dictionary it is. Only the pages of the NHK accent dictionary parse
+/// into `DicItem`s, the others can be looked up and read as XML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Product {
+    NhkAccent,
+    Other,
+}
+
+/// The metadata of a dictionary, see `MonokakidoDict::info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictInfo {
+    /// None for a content directory without the product metadata.
+    pub title: Option<String>,
+    /// Like `jp.monokakido.Dictionaries.NHKACCENT2`.
+    pub identifier: Option<String>,
+    pub product: Product,
+    /// Where `contents/`, `key/` and so on are, `""` if the dictionary is the
+    /// content directory itself.
+    pub content_dir: String,
+    pub audio: bool,
+    pub headlines: bool,
+    /// The files in `key/`, sorted, like `headword.keyindex`.
+    pub key_indexes: Vec<String>,
+}
+
+impl DictInfo {
+    pub fn has_key_index(&self, name: &str) -> bool {
+        self.key_indexes.iter().any(|k| k == name)
+    }
+}
+
+#[derive(Deserialize)]
+struct ProductJson {
+    #[serde(rename = "DSProductTitle")]
+    title: Option<String>,
+    #[serde(rename = "DSProductIdentifier")]
+    identifier: Option<String>,
+    #[serde(rename = "DSProductContents")]
+    contents: Vec<ProductContents>,
+}
+
+#[derive(Deserialize)]
+struct ProductContents {
+    #[serde(rename = "DSContentDirectory")]
+    dir: String,
+}
+
+/// The metadata of the dictionary in `storage` and its content directory.
+pub(crate) fn detect(storage: Arc<dyn Storage>) -> Result<(DictInfo, Arc<dyn Storage>), Error> {
+    let (title, identifier, content_dir) = match find_product_json(&*storage)? {
+        Some(path) => {
+            let json = read_product_json(&*storage, &path)?;
+            // products with several content directories list the dictionary first
+            let dir = json.contents.first().ok_or(Error::InvalidDictJson)?;
+            (json.title, json.identifier, join(PRODUCT_DIR, &dir.dir))
+        }
+        None => (None, None, String::new()),
+    };
+    let content: Arc<dyn Storage> = if content_dir.is_empty() {
+        storage
+    } else {
+        Arc::new(SubDir::new(storage, &content_dir))
+    };
+
+    // the add-on's assets are the NHK dictionary
+    let name = identifier.as_deref().unwrap_or(&content_dir);
+    let product = if content_dir.is_empty() || name.contains(NHK_ACCENT) {
+        Product::NhkAccent
+    } else {
+        Product::Other
+    };
+    let audio = content
+        .list("audio")
+        .is_ok_and(|names| names.iter().any(|n| n == "index.nidx" || n == "audio.map"));
+    let headlines = content
+        .list("headline")
+        .is_ok_and(|names| !names.is_empty());
+    let mut key_indexes: Vec<String> = content
+        .list("key")
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| name.ends_with(".keyindex"))
+        .collect();
+    key_indexes.sort();
+
+    let info = DictInfo {
+        title,
+        identifier,
+        product,
+        content_dir,
+        audio,
+        headlines,
+        key_indexes,
+    };
+    Ok((info, content))
+}
+
+/// The content directory of the dictionary at `path`.
+pub(crate) fn open_content(path: &Path, mode: LoadMode) -> Result<Arc<dyn Storage>, Error> {
+    detect(open_path(path, mode)?).map(|(_, content)| content)
+}
+
+/// The path of the product JSON, None if `storage` is a content directory.
+fn find_product_json(storage: &dyn Storage) -> Result<Option<String>, Error> {
+    let Ok(mut names) = storage.list(PRODUCT_DIR) else {
+        return Ok(None);
+    };
+    names.sort();
+    if let Some(name) = names.iter().find(|n| n.ends_with(".json")) {
+        return Ok(Some(join(PRODUCT_DIR, name)));
+    }
+    // `Contents/` and `contents/` are the same on case insensitive file systems
+    if names.is_empty() || storage.exists("contents/contents.map") {
+        return Ok(None);
+    }
+    Err(Error::NoDictJsonFound)
+}
+
+fn read_product_json(storage: &dyn Storage, path: &str) -> Result<ProductJson, Error> {
+    let file = storage.open(path)?;
+    let mut json = vec![0; file.len() as usize];
+    file.read_exact_at(&mut json, 0)?;
+    serde_json::from_slice(&json).map_err(|_| Error::InvalidDictJson)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MonokakidoDict,
+        dict::{HEADWORD_KEYS, NUMERAL_KEYS},
+        fixture::{SAKURA_SOUND, dict_files},
+        storage::MemStorage,
+    };
+
+    const JSON: &str = r#"{
+        "DSProductTitle": "NHK日本語発音アクセント新辞典",
+        "DSProductIdentifier": "jp.monokakido.Dictionaries.NHKACCENT2",
+        "DSProductVersion": "1.0",
+        "DSProductContents": [{"DSContentDirectory": "NHKACCENT2", "DSContentType": "dictionary"}]
+    }"#;
+
+    /// The fixture as a product, without the files `skip` matches.
+    fn product(name: &str, json: Option<&str>, skip: impl Fn(&str) -> bool) -> Arc<dyn Storage> {
+        let mut storage = MemStorage::new();
+        for (path, data) in dict_files(name) {
+            if !skip(&path) {
+                storage.insert(&format!("Contents/NHKACCENT2/{path}"), data);
+            }
+        }
+        if let Some(json) = json {
+            storage.insert("Contents/NHKACCENT2.json", json.as_bytes().to_vec());
+        }
+        storage.insert("Contents/Info.plist", b"<plist/>".to_vec());
+        Arc::new(storage)
+    }
+
+    #[test]
+    fn content_dir() {
+        let mut storage = MemStorage::new();
+        for (path, data) in dict_files("info_content") {
+            storage.insert(&path, data);
+        }
+        let dict = MonokakidoDict::open_with_storage(Arc::new(storage)).unwrap();
+        let info = &dict.info;
+        assert_eq!(info.title, None);
+        assert_eq!(info.product, Product::NhkAccent);
+        assert_eq!(info.content_dir, "");
+        assert!(info.audio && info.headlines);
+        assert_eq!(
+            info.key_indexes,
+            ["compound.keyindex", "headword.keyindex", "numeral.keyindex"]
+        );
+    }
+
+    #[test]
+    fn product_json() {
+        let storage = product("info_product", Some(JSON), |_| false);
+        let dict = MonokakidoDict::open_with_storage(storage).unwrap();
+        let info = &dict.info;
+        assert_eq!(info.title.as_deref(), Some("NHK日本語発音アクセント新辞典"));
+        assert_eq!(
+            info.identifier.as_deref(),
+            Some("jp.monokakido.Dictionaries.NHKACCENT2")
+        );
+        assert_eq!(info.product, Product::NhkAccent);
+        assert_eq!(info.content_dir, "Contents/NHKACCENT2");
+        assert!(info.has_key_index(NUMERAL_KEYS));
+        assert_eq!(dict.look_up("サクラ", None).unwrap().len(), 1);
+        assert!(dict.audio.get(SAKURA_SOUND).is_ok());
+
+        let json = r#"{"DSProductIdentifier": "jp.monokakido.Dictionaries.DAIJIRIN",
+            "DSProductContents": [{"DSContentDirectory": "NHKACCENT2"}]}"#;
+        let storage = product("info_other", Some(json), |_| false);
+        let (info, _) = detect(storage).unwrap();
+        assert_eq!(info.product, Product::Other);
+        assert_eq!(info.title, None);
+    }
+
+    #[test]
+    fn missing_parts() {
+        let storage = product("info_missing", Some(JSON), |path| {
+            path.starts_with("audio/") || path.ends_with(NUMERAL_KEYS)
+        });
+        let dict = MonokakidoDict::open_with_storage(storage).unwrap();
+        assert!(!dict.info.audio);
+        assert!(dict.info.has_key_index(HEADWORD_KEYS));
+        assert!(!dict.info.has_key_index(NUMERAL_KEYS));
+        assert_eq!(dict.look_up("サクラ", None).unwrap().len(), 1);
+        assert!(dict.look_up("イッポン", None).unwrap().is_empty());
+        assert!(dict.numeral_keys.search_prefix("").unwrap().is_empty());
+        assert!(dict.audio.get(SAKURA_SOUND).is_err());
+    }
+
+    #[test]
+    fn broken_product() {
+        let storage = product("info_no_json", None, |_| false);
+        assert_eq!(
+            MonokakidoDict::open_with_storage(storage).err(),
+            Some(Error::NoDictJsonFound)
+        );
+        for json in [
+            "{",
+            r#"{"DSProductContents": []}"#,
+            r#"{"DSProductTitle": "x"}"#,
+        ] {
+            let storage = product("info_bad_json", Some(json), |_| false);
+            assert_eq!(
+                MonokakidoDict::open_with_storage(storage).err(),
+                Some(Error::InvalidDictJson)
+            );
+        }
+    }
+}
@@ -1,18 +1,16 @@
-use std::{
-    borrow::Cow,
-    cmp::Ordering,
-    fs::File,
-    io::{Read, Seek},
-    mem::size_of,
-    path::PathBuf,
-    str::from_utf8,
-};
+use std::{borrow::Cow, cmp::Ordering, mem::size_of, path::Path, str::from_utf8};
 
 use crate::{
     Error,
-    abi_utils::{LE32, TransmuteSafe, read_vec},
+    abi_utils::{LE32, LoadMode, Region, TransmuteSafe, load_region},
+    info::open_content,
+    storage::Storage,
 };
 
+mod writer;
+
+pub use writer::KeysWriter;
+
 mod abi {
     use super::*;
 
@@ -30,6 +28,15 @@ mod abi {
     }
 
     impl FileHeader {
+        pub(super) fn new(words_offset: u32, idx_offset: u32) -> Self {
+            FileHeader {
+                magic1: 0x20000.into(),
+                words_offset: words_offset.into(),
+                idx_offset: idx_offset.into(),
+                ..Default::default()
+            }
+        }
+
         pub(super) fn validate(&self) -> Result<(), Error> {
             if self.magic1.read() == 0x20000
                 && self.magic2.read() == 0
@@ -59,6 +66,16 @@ mod abi {
     }
 
     impl IndexHeader {
+        pub(super) fn new(offsets: [u32; 4]) -> Self {
+            IndexHeader {
+                magic1: 0x04.into(),
+                index_a_offset: offsets[0].into(),
+                index_b_offset: offsets[1].into(),
+                index_c_offset: offsets[2].into(),
+                index_d_offset: offsets[3].into(),
+            }
+        }
+
         pub(super) fn validate(&self, idx_end: usize) -> Result<(), Error> {
             let a = self.index_a_offset.us();
             let b = self.index_b_offset.us();
@@ -84,11 +101,11 @@ use abi::{FileHeader, IndexHeader};
 
 #[derive(Debug)]
 pub struct KeyIndex {
-    index: Option<Vec<LE32>>,
+    index: Option<Region<LE32>>,
 }
 
 pub struct Keys {
-    words: Vec<LE32>,
+    words: Region<LE32>,
     pub index_len: KeyIndex,
     pub index_prefix: KeyIndex,
     pub index_suffix: KeyIndex,
@@ -108,12 +125,15 @@ impl KeyIndex {
     }
 
     pub fn len(&self) -> usize {
-        self.index.as_ref().map(|v| v.len()).unwrap_or(0) - 1
+        self.index
+            .as_ref()
+            .map(|v| v.len().saturating_sub(1))
+            .unwrap_or(0)
     }
 }
 
 impl Keys {
-    fn check_vec_len(buf: &Option<Vec<LE32>>) -> Result<(), Error> {
+    fn check_vec_len(buf: &Option<Region<LE32>>) -> Result<(), Error> {
         let Some(buf) = buf else { return Ok(()) };
         if buf.first().ok_or(Error::InvalidIndex)?.us() + 1 != buf.len() {
             return Err(Error::InvalidIndex);
@@ -121,47 +141,70 @@ impl Keys {
         Ok(())
     }
 
+    /// Keys without any words, for a key index the dictionary doesn't have.
+    pub fn empty() -> Keys {
+        Keys {
+            words: Vec::new().into(),
+            index_len: KeyIndex { index: None },
+            index_prefix: KeyIndex { index: None },
+            index_suffix: KeyIndex { index: None },
+            index_d: KeyIndex { index: None },
+        }
+    }
+
     pub fn new(path: &str, key_index: &str) -> Result<Keys, Error> {
-        let mut file = File::open(PathBuf::from(path).join("key/").join(key_index))?;
-        let file_size = file.metadata()?.len() as usize;
+        Self::with_mode(path, key_index, LoadMode::default())
+    }
+
+    pub fn with_mode(path: &str, key_index: &str, mode: LoadMode) -> Result<Keys, Error> {
+        Self::with_storage(&*open_content(Path::new(path), mode)?, key_index)
+    }
+
+    /// The key index `key/{key_index}` of `storage`.
+    pub fn with_storage(storage: &dyn Storage, key_index: &str) -> Result<Keys, Error> {
+        let file = storage.open(&format!("key/{key_index}"))?;
+        let file = &*file;
+        let file_size = file.len() as usize;
         let mut hdr = FileHeader::default();
-        file.read_exact(hdr.as_bytes_mut())?;
+        file.read_exact_at(hdr.as_bytes_mut(), 0)?;
         hdr.validate()?;
 
-        file.seek(std::io::SeekFrom::Start(hdr.words_offset.read() as u64))?;
-        let words = read_vec(&mut file, hdr.words_offset.us(), hdr.idx_offset.us())?;
+        let words = load_region(file, 0, hdr.words_offset.us(), hdr.idx_offset.us())?;
         let Some(words) = words else {
             return Err(Error::InvalidIndex);
         };
 
         let idx_end = file_size - hdr.idx_offset.us();
         let mut ihdr = IndexHeader::default();
-        file.seek(std::io::SeekFrom::Start(hdr.idx_offset.read() as u64))?;
-        file.read_exact(ihdr.as_bytes_mut())?;
+        file.read_exact_at(ihdr.as_bytes_mut(), hdr.idx_offset.read() as u64)?;
         ihdr.validate(idx_end)?;
 
-        let index_a = read_vec(
-            &mut file,
+        let idx_offset = hdr.idx_offset.us();
+        let index_a = load_region(
+            file,
+            idx_offset,
             ihdr.index_a_offset.us(),
             ihdr.index_b_offset.us(),
         )?;
         Self::check_vec_len(&index_a)?;
 
-        let index_b = read_vec(
-            &mut file,
+        let index_b = load_region(
+            file,
+            idx_offset,
             ihdr.index_b_offset.us(),
             ihdr.index_c_offset.us(),
         )?;
         Self::check_vec_len(&index_b)?;
 
-        let index_c = read_vec(
-            &mut file,
+        let index_c = load_region(
+            file,
+            idx_offset,
             ihdr.index_c_offset.us(),
             ihdr.index_d_offset.us(),
         )?;
         Self::check_vec_len(&index_c)?;
 
-        let index_d = read_vec(&mut file, ihdr.index_d_offset.us(), idx_end)?;
+        let index_d = load_region(file, idx_offset, ihdr.index_d_offset.us(), idx_end)?;
         Self::check_vec_len(&index_d)?;
 
         Ok(Keys {
@@ -174,7 +217,9 @@ impl Keys {
     }
 
     fn get_page_iter(&self, pages_offset: usize) -> Result<PageIter<'_>, Error> {
-        let pages = &LE32::slice_as_bytes(&self.words)[pages_offset..];
+        let pages = LE32::slice_as_bytes(&self.words)
+            .get(pages_offset..)
+            .ok_or(Error::InvalidIndex)?;
         PageIter::new(pages)
     }
 
@@ -192,24 +237,16 @@ impl Keys {
         }
     }
 
+    /// Compares the word at `idx` of the prefix index with `target`, byte by byte.
     pub(crate) fn cmp_key(&self, target: &str, idx: usize) -> Result<Ordering, Error> {
         let offset = self.index_prefix.get(idx)? + size_of::<LE32>() + 1;
         let words_bytes = LE32::slice_as_bytes(&self.words);
-        if words_bytes.len() < offset + target.len() + 1 {
-            return Err(Error::InvalidIndex); // Maybe just return Ordering::Less instead?
-        }
-        let found_tail = &words_bytes[offset..];
-        let found = &found_tail[..target.len()];
-        Ok(match found.cmp(target.as_bytes()) {
-            Ordering::Equal => {
-                if found_tail[target.len()] == b'\0' {
-                    Ordering::Equal
-                } else {
-                    Ordering::Greater
-                }
-            }
-            ord => ord,
-        })
+        let found_tail = words_bytes.get(offset..).ok_or(Error::InvalidIndex)?;
+        let found_len = found_tail
+            .iter()
+            .position(|b| *b == b'\0')
+            .ok_or(Error::InvalidIndex)?;
+        Ok(found_tail[..found_len].cmp(target.as_bytes()))
     }
 
     pub fn get_idx(&self, index: &KeyIndex, idx: usize) -> Result<(&str, PageIter<'_>), Error> {
@@ -225,27 +262,89 @@ impl Keys {
 
     pub fn search_exact(&self, target_key: &str) -> Result<(usize, PageIter<'_>), Error> {
         let target_key = &to_katakana(target_key);
-        let mut high = self.index_prefix.len();
+        // the key is in `low..high`, if it exists
         let mut low = 0;
+        let mut high = self.index_prefix.len();
 
-        // TODO: Revise corner cases and add tests for this binary search
-        while low <= high {
+        while low < high {
             let mid = low + (high - low) / 2;
 
             let cmp = self.cmp_key(target_key, mid)?;
 
             match cmp {
                 Ordering::Less => low = mid + 1,
-                Ordering::Greater => high = mid - 1,
+                Ordering::Greater => high = mid,
                 Ordering::Equal => return Ok((mid, self.get_idx(&self.index_prefix, mid)?.1)),
             }
         }
 
         Err(Error::NotFound)
     }
+
+    /// Words starting with `prefix`, in the order of the prefix index.
+    pub fn search_prefix(&self, prefix: &str) -> Result<Vec<(&str, PageIter<'_>)>, Error> {
+        let prefix = &to_katakana(prefix);
+        // first word that is not less than `prefix`
+        let mut low = 0;
+        let mut high = self.index_prefix.len();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.cmp_key(prefix, mid)? {
+                Ordering::Less => low = mid + 1,
+                _ => high = mid,
+            }
+        }
+
+        let mut found = Vec::new();
+        for idx in low..self.index_prefix.len() {
+            let (word, pages) = self.get_idx(&self.index_prefix, idx)?;
+            if !word.starts_with(prefix.as_ref()) {
+                break;
+            }
+            found.push((word, pages));
+        }
+        Ok(found)
+    }
+
+    /// Words ending with `suffix`. Scans all words.
+    pub fn search_suffix(&self, suffix: &str) -> Result<Vec<(&str, PageIter<'_>)>, Error> {
+        let suffix = to_katakana(suffix);
+        self.search_by(|word| word.ends_with(suffix.as_ref()))
+    }
+
+    /// Words matching `pattern`, where `?` stands for one character and `*` for
+    /// any number of characters. Scans all words.
+    pub fn search_pattern(&self, pattern: &str) -> Result<Vec<(&str, PageIter<'_>)>, Error> {
+        let pattern: Vec<char> = to_katakana(pattern).chars().collect();
+        self.search_by(|word| {
+            let word: Vec<char> = word.chars().collect();
+            wildcard_match(&pattern, &word)
+        })
+    }
+
+    fn search_by(&self, f: impl Fn(&str) -> bool) -> Result<Vec<(&str, PageIter<'_>)>, Error> {
+        let mut found = Vec::new();
+        for idx in 0..self.index_prefix.len() {
+            let (word, pages) = self.get_idx(&self.index_prefix, idx)?;
+            if f(word) {
+                found.push((word, pages));
+            }
+        }
+        Ok(found)
+    }
 }
 
-fn to_katakana(input: &str) -> Cow<'_, str> {
+/// Matches `word` against a pattern with `?` and `*` wildcards.
+fn wildcard_match(pattern: &[char], word: &[char]) -> bool {
+    match pattern.split_first() {
+        None => word.is_empty(),
+        Some(('*', rest)) => (0..=word.len()).any(|skip| wildcard_match(rest, &word[skip..])),
+        Some(('?', rest)) => !word.is_empty() && wildcard_match(rest, &word[1..]),
+        Some((c, rest)) => word.first() == Some(c) && wildcard_match(rest, &word[1..]),
+    }
+}
+
+pub(crate) fn to_katakana(input: &str) -> Cow<'_, str> {
     let diff = 'ア' as u32 - 'あ' as u32;
     if let Some(pos) = input.find(|c| matches!(c, 'ぁ'..='ん')) {
         let mut output = input[..pos].to_owned();
@@ -279,6 +378,19 @@ fn test_to_katakana() {
     assert_eq!(*to_katakana("aッa"), *"aッa");
 }
 
+#[test]
+fn test_page_iter_bounds() {
+    assert_eq!(PageIter::new(&[]).err(), Some(Error::InvalidIndex));
+    assert_eq!(PageIter::new(&[1]).err(), Some(Error::InvalidIndex));
+    // two ids announced, one and a half there
+    assert_eq!(
+        PageIter::new(&[2, 0, 1, 5, 2, 0]).err(),
+        Some(Error::InvalidIndex)
+    );
+    let pages = PageIter::new(&[1, 0, 2, 0x12, 0x34, 7]).unwrap();
+    assert_eq!(pages.collect::<Vec<_>>(), [pid([0, 0x12, 0x34], 0)]);
+}
+
 #[derive(Debug, Clone)]
 pub struct PageIter<'a> {
     count: u16,
@@ -287,22 +399,19 @@ pub struct PageIter<'a> {
 
 impl<'a> PageIter<'a> {
     fn new(pages: &'a [u8]) -> Result<Self, Error> {
-        let (count, pages) = pages.split_at(2);
-        let count = u16::from_le_bytes(count.try_into().unwrap());
+        let (count, pages) = pages.split_first_chunk().ok_or(Error::InvalidIndex)?;
+        let count = u16::from_le_bytes(*count);
 
         // CHECK INVARIANT B: loop through `count` times and check that the shape is of expected
         let mut tail = pages;
         for _ in 0..count {
-            match tail {
-                &[1, _, ref t @ ..] => tail = t,
-                &[2, _, _, ref t @ ..] => tail = t,
-                &[4, _, _, _, ref t @ ..] => tail = t,
-                &[17, _, _, ref t @ ..] => tail = t,
-                &[18, _, _, _, ref t @ ..] => tail = t,
-                e => {
-                    dbg!("hmm", &e[..100]); // TODO: clean this up
-                    return Err(Error::InvalidIndex);
-                }
+            match *tail {
+                [1, _, ref t @ ..] => tail = t,
+                [2, _, _, ref t @ ..] => tail = t,
+                [4, _, _, _, ref t @ ..] => tail = t,
+                [17, _, _, ref t @ ..] => tail = t,
+                [18, _, _, _, ref t @ ..] => tail = t,
+                _ => return Err(Error::InvalidIndex),
             }
         }
         let span_len = pages.len() - tail.len();
@@ -335,7 +444,7 @@ impl<'a> Iterator for PageIter<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PageItemId {
     pub page: u32,
     pub item: u8,
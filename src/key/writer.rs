@@ -0,0 +1,282 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    mem::size_of,
+    path::PathBuf,
+};
+
+use super::{FileHeader, IndexHeader, PageItemId, to_katakana};
+use crate::{
+    Error,
+    abi_utils::{LE32, TransmuteSafe},
+};
+
+/// Builds a `.keyindex` file that `Keys` reads: the word table with the page
+/// references of every word, followed by the four word indexes.
+#[derive(Debug, Default)]
+pub struct KeysWriter {
+    // sorted by bytes, which is the order `Keys::search_exact` expects
+    words: BTreeMap<String, Vec<PageItemId>>,
+}
+
+impl KeysWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds page references for `word`. Hiragana is stored as katakana, the same
+    /// way `Keys::search_exact` looks words up. Adding a word again extends its pages.
+    pub fn add(&mut self, word: &str, pages: impl IntoIterator<Item = PageItemId>) {
+        self.words
+            .entry(to_katakana(word).into_owned())
+            .or_default()
+            .extend(pages);
+    }
+
+    /// Writes `key/{key_index}` below the dictionary directory `path`.
+    pub fn write(&self, path: &str, key_index: &str) -> Result<(), Error> {
+        let dir = PathBuf::from(path).join("key/");
+        fs::create_dir_all(&dir)?;
+        let mut file = BufWriter::new(File::create(dir.join(key_index))?);
+        file.write_all(&self.to_bytes()?)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let words: Vec<&str> = self.words.keys().map(String::as_str).collect();
+
+        // Word entries come first, the page lists they point to after them.
+        // All offsets in the table are relative to its start.
+        let mut word_offsets = Vec::with_capacity(words.len());
+        let mut entries_len = 0;
+        for word in &words {
+            word_offsets.push(entries_len);
+            entries_len += size_of::<LE32>() + 1 + word.len() + 1;
+        }
+        let mut table = Vec::new();
+        let mut pages = Vec::new();
+        for (word, page_ids) in &self.words {
+            let pages_offset = to_u32(entries_len + pages.len())?;
+            table.extend_from_slice(&pages_offset.to_le_bytes());
+            // unknown, always zero
+            table.push(0);
+            table.extend_from_slice(word.as_bytes());
+            table.push(b'\0');
+            encode_pages(page_ids, &mut pages)?;
+        }
+        table.append(&mut pages);
+        // `Keys` rejects an empty table, even if there are no words
+        let table_len = table.len().next_multiple_of(size_of::<LE32>());
+        table.resize(table_len.max(size_of::<LE32>()), 0);
+
+        let by_len = sorted_by(&words, |w| (w.chars().count(), w));
+        let by_prefix: Vec<usize> = (0..words.len()).collect();
+        let by_suffix = sorted_by(&words, |w| w.chars().rev().collect::<String>());
+        // the order of the fourth index is not known, it is written like the prefix index
+        let indexes = [by_len, by_prefix.clone(), by_suffix, by_prefix];
+
+        let mut index_offsets = [0; 4];
+        let mut offset = size_of::<IndexHeader>();
+        for index_offset in &mut index_offsets {
+            *index_offset = to_u32(offset)?;
+            offset += (words.len() + 1) * size_of::<LE32>();
+        }
+
+        let words_offset = size_of::<FileHeader>();
+        let idx_offset = words_offset + table.len();
+        let mut out = Vec::with_capacity(idx_offset + offset);
+        out.extend_from_slice(
+            FileHeader::new(to_u32(words_offset)?, to_u32(idx_offset)?).as_bytes(),
+        );
+        out.append(&mut table);
+        out.extend_from_slice(IndexHeader::new(index_offsets).as_bytes());
+        for index in indexes {
+            out.extend_from_slice(&to_u32(index.len())?.to_le_bytes());
+            for i in index {
+                out.extend_from_slice(&to_u32(word_offsets[i])?.to_le_bytes());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Positions of `words`, ordered by `key`.
+fn sorted_by<'a, K: Ord>(words: &[&'a str], key: impl Fn(&'a str) -> K) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..words.len()).collect();
+    order.sort_by_key(|i| key(words[*i]));
+    order
+}
+
+/// Encodes a page list the way `PageIter` decodes it, using the shortest form for every id.
+fn encode_pages(ids: &[PageItemId], out: &mut Vec<u8>) -> Result<(), Error> {
+    let count = u16::try_from(ids.len()).map_err(|_| Error::RecordTooLarge)?;
+    out.extend_from_slice(&count.to_le_bytes());
+    for id in ids {
+        let [_, hi, mid, lo] = id.page.to_be_bytes();
+        match (id.page, id.item) {
+            (0..=0xff, 0) => out.extend_from_slice(&[1, lo]),
+            (0..=0xffff, 0) => out.extend_from_slice(&[2, mid, lo]),
+            (0..=0xff_ffff, 0) => out.extend_from_slice(&[4, hi, mid, lo]),
+            (0..=0xff, item) => out.extend_from_slice(&[17, lo, item]),
+            (0..=0xffff, item) => out.extend_from_slice(&[18, mid, lo, item]),
+            _ => return Err(Error::InvalidArg),
+        }
+    }
+    Ok(())
+}
+
+fn to_u32(n: usize) -> Result<u32, Error> {
+    u32::try_from(n).map_err(|_| Error::RecordTooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Keys, LoadMode, fixture::temp_dir, key::PageIter};
+
+    fn pid(page: u32, item: u8) -> PageItemId {
+        PageItemId { page, item }
+    }
+
+    #[test]
+    fn page_encoding() {
+        let mut out = Vec::new();
+        let ids = [
+            pid(5, 0),
+            pid(0x1234, 0),
+            pid(0x12_3456, 0),
+            pid(5, 2),
+            pid(0x1234, 3),
+        ];
+        encode_pages(&ids, &mut out).unwrap();
+        assert_eq!(
+            out,
+            [
+                5, 0, 1, 5, 2, 0x12, 0x34, 4, 0x12, 0x34, 0x56, 17, 5, 2, 18, 0x12, 0x34, 3
+            ]
+        );
+        let mut out = Vec::new();
+        assert_eq!(
+            encode_pages(&[pid(0x12_3456, 1)], &mut out),
+            Err(Error::InvalidArg)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_dir("keys_writer");
+        let path = dir.to_str().unwrap();
+        let mut writer = KeysWriter::new();
+        writer.add("サクラ", [pid(10, 0)]);
+        writer.add("あめ", [pid(0x1234, 0), pid(0x1234, 2)]);
+        writer.add("アメ", [pid(300_000, 0)]);
+        writer.add("ア", [pid(1, 1)]);
+        writer.add("ゴールデンウィーク", [pid(7, 0)]);
+        writer.write(path, "headword.keyindex").unwrap();
+
+        for mode in [LoadMode::Mmap, LoadMode::Read] {
+            let keys = Keys::with_mode(path, "headword.keyindex", mode).unwrap();
+            assert_eq!(keys.index_prefix.len(), 4);
+            assert_eq!(keys.index_len.len(), 4);
+
+            let (_, pages) = keys.search_exact("アメ").unwrap();
+            assert_eq!(
+                pages.collect::<Vec<_>>(),
+                [pid(0x1234, 0), pid(0x1234, 2), pid(300_000, 0)]
+            );
+            let (_, pages) = keys.search_exact("さくら").unwrap();
+            assert_eq!(pages.collect::<Vec<_>>(), [pid(10, 0)]);
+            let (_, pages) = keys.search_exact("ア").unwrap();
+            assert_eq!(pages.collect::<Vec<_>>(), [pid(1, 1)]);
+
+            let (word, _) = keys.get_idx(&keys.index_len, 0).unwrap();
+            assert_eq!(word, "ア");
+            let (word, _) = keys.get_idx(&keys.index_len, 3).unwrap();
+            assert_eq!(word, "ゴールデンウィーク");
+            let (word, _) = keys.get_idx(&keys.index_suffix, 0).unwrap();
+            assert_eq!(word, "ア");
+        }
+    }
+
+    #[test]
+    fn prefix_suffix_and_pattern() {
+        let dir = temp_dir("keys_writer_patterns");
+        let path = dir.to_str().unwrap();
+        let mut writer = KeysWriter::new();
+        for (i, word) in ["アメ", "アメダマ", "アサ", "ハナミ", "ツキミ", "ア"]
+            .iter()
+            .enumerate()
+        {
+            writer.add(word, [pid(i as u32, 0)]);
+        }
+        writer.write(path, "headword.keyindex").unwrap();
+        let keys = Keys::new(path, "headword.keyindex").unwrap();
+        let words = |found: Vec<(&str, PageIter)>| -> Vec<String> {
+            found.into_iter().map(|(w, _)| w.to_string()).collect()
+        };
+
+        assert_eq!(
+            words(keys.search_prefix("あめ").unwrap()),
+            ["アメ", "アメダマ"]
+        );
+        assert_eq!(
+            words(keys.search_prefix("ア").unwrap()),
+            ["ア", "アサ", "アメ", "アメダマ"]
+        );
+        assert!(keys.search_prefix("ユ").unwrap().is_empty());
+        assert!(keys.search_prefix("ンン").unwrap().is_empty());
+        assert_eq!(
+            words(keys.search_suffix("ミ").unwrap()),
+            ["ツキミ", "ハナミ"]
+        );
+        assert_eq!(words(keys.search_pattern("ア?").unwrap()), ["アサ", "アメ"]);
+        assert_eq!(
+            words(keys.search_pattern("*ミ").unwrap()),
+            ["ツキミ", "ハナミ"]
+        );
+        assert_eq!(words(keys.search_pattern("ア*マ").unwrap()), ["アメダマ"]);
+        assert_eq!(words(keys.search_pattern("*").unwrap()).len(), 6);
+
+        let (_, mut pages) = keys.search_prefix("アメダ").unwrap().remove(0);
+        assert_eq!(pages.next(), Some(pid(1, 0)));
+    }
+
+    #[test]
+    fn search_corner_cases() {
+        let dir = temp_dir("keys_writer_search");
+        let path = dir.to_str().unwrap();
+
+        KeysWriter::new().write(path, "empty.keyindex").unwrap();
+        let keys = Keys::new(path, "empty.keyindex").unwrap();
+        assert_eq!(keys.search_exact("ア").err(), Some(Error::NotFound));
+        assert_eq!(keys.search_exact("").err(), Some(Error::NotFound));
+
+        let mut writer = KeysWriter::new();
+        writer.add("イ", [pid(1, 0)]);
+        writer.write(path, "one.keyindex").unwrap();
+        let keys = Keys::new(path, "one.keyindex").unwrap();
+        assert!(keys.search_exact("イ").is_ok());
+        // before the first, after the last and longer than any stored word
+        assert_eq!(keys.search_exact("ア").err(), Some(Error::NotFound));
+        assert_eq!(keys.search_exact("ウ").err(), Some(Error::NotFound));
+        assert_eq!(keys.search_exact("イイ").err(), Some(Error::NotFound));
+        let long = "イ".repeat(100);
+        assert_eq!(keys.search_exact(&long).err(), Some(Error::NotFound));
+
+        let mut writer = KeysWriter::new();
+        let words: Vec<String> = (1..50).map(|n| "カ".repeat(n)).collect();
+        for (i, word) in words.iter().enumerate() {
+            writer.add(word, [pid(i as u32, 0)]);
+        }
+        writer.write(path, "many.keyindex").unwrap();
+        let keys = Keys::new(path, "many.keyindex").unwrap();
+        for (i, word) in words.iter().enumerate() {
+            let (_, mut pages) = keys.search_exact(word).unwrap();
+            assert_eq!(pages.next(), Some(pid(i as u32, 0)));
+        }
+        assert_eq!(keys.search_exact("カキ").err(), Some(Error::NotFound));
+        assert_eq!(keys.search_exact("").err(), Some(Error::NotFound));
+    }
+}
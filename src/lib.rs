@@ -1,111 +1,83 @@
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 mod abi_utils;
 mod audio;
 mod circle;
+mod coverage;
 mod dict;
 mod error;
+mod export;
+#[cfg(test)]
+mod fixture;
 pub mod gen_svg;
 mod headline;
+pub mod hum;
+mod info;
 mod key;
 mod pages;
+pub mod pitch;
 mod pxml;
+#[cfg(feature = "python")]
+mod python;
+mod render;
 mod resource;
+mod storage;
+mod verify;
+pub mod waveform;
 
+pub use abi_utils::LoadMode;
 pub use audio::Audio;
+pub use coverage::{Coverage, UnknownClass, coverage};
 pub use dict::MonokakidoDict;
 pub use error::Error;
+pub use export::{
+    AnkiWriter, Column, CsvWriter, Entry, EntryAccent, Exported, JSON_VERSION, SkippedPage,
+    YomitanWriter, for_each_entry, write_jsonl, write_sqlite,
+};
 pub use headline::Headlines;
-pub use key::{KeyIndex, Keys, PageItemId};
+pub use info::{DictInfo, Product};
+pub use key::{KeyIndex, Keys, KeysWriter, PageItemId, PageIter};
 pub use pages::{Pages, XmlParser};
 pub use pxml::*;
+pub use render::{HtmlRenderer, TextRenderer};
+pub use resource::{CacheStats, NrscFormat, NrscWriter, RscWriter};
+pub use storage::{Bytes, DirStorage, MemStorage, ReadAt, Storage, ZipStorage};
+pub use verify::{Problem, Report, verify};
 
-#[pyfunction(signature = (path, vocab, word_type=None))]
-fn look_up(path: String, vocab: String, word_type: Option<WordType>) -> Vec<Unpacked> {
-    _look_up(&path, &vocab, word_type)
-}
-
-#[pyfunction]
-fn gen_pitch_svg(pitch_pattern: String) -> String {
-    gen_svg::gen_svg(&pitch_pattern)
-}
-
-#[pyfunction]
-fn get_sound(path: String, file_name: String) -> Vec<u8> {
-    let file_name = file_name.strip_suffix(".aac").unwrap_or(&file_name);
-    let mut dict = MonokakidoDict::open_with_path(&path).unwrap();
-    dict.audio.get(file_name).unwrap().to_vec()
-}
-
-/// A Python module implemented in Rust.
-#[pymodule]
-fn accent_dict(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(look_up, m)?)?;
-    m.add_function(wrap_pyfunction!(get_sound, m)?)?;
-    m.add_function(wrap_pyfunction!(gen_pitch_svg, m)?)?;
-    m.add_class::<WordType>()?;
-    Ok(())
-}
+#[cfg(any(feature = "python", test))]
+pub(crate) fn _look_up(
+    path: &str,
+    vocab: &str,
+    word_type: Option<WordType>,
+) -> Result<Vec<Unpacked>, Error> {
+    let dict = MonokakidoDict::open_with_path(path)?;
+    let pages = dict::find_pages(&dict, vocab, word_type)?;
+    if pages.is_empty() {
+        return Ok(vec![Unpacked {
+            id: "0".to_string(),
+            head: "<not found>".to_string(),
+            ..Default::default()
+        }]);
+    }
 
-fn _look_up(path: &str, vocab: &str, word_type: Option<WordType>) -> Vec<Unpacked> {
-    let mut dict = match MonokakidoDict::open_with_path(path) {
-        Ok(dict) => dict,
-        Err(_e) => return Vec::new(),
-    };
     let mut unpacked: Vec<Unpacked> = Vec::new();
-
-    // is dict index
-    if vocab.starts_with(|c: char| c.is_ascii_digit()) {
-        let index: usize = vocab
-            .chars()
-            .take_while(|c: &char| c.is_ascii_digit())
-            .collect::<String>()
-            .parse()
-            .unwrap();
-        //I don't know why it is off by one
-        let index = index.saturating_sub(1);
-        let (_, page) = dict.pages.page_by_idx(index).unwrap();
-        let parsed = parse_xml(page);
-        println!("{parsed:#?}");
+    for parsed in dict::parse_pages(pages)? {
         unpacked.append(&mut unpack_dic_item(parsed))
-
-    // is vocab
-    } else {
-        let mut pages = Vec::new();
-
-        let search_headword = word_type.is_none() || word_type == Some(WordType::HEADWORD);
-        let search_compound = word_type.is_none() || word_type == Some(WordType::COMPOUND);
-        let search_numeral = word_type.is_none() || word_type == Some(WordType::COUNTER);
-
-        if search_headword && let Ok((_, hw_pages)) = dict.headword_keys.search_exact(vocab) {
-            pages.push(hw_pages);
-        }
-        if search_compound && let Ok((_, compound_pages)) = dict.compound_keys.search_exact(vocab) {
-            pages.push(compound_pages);
-        }
-        if search_numeral && let Ok((_, numeral_pages)) = dict.numeral_keys.search_exact(vocab) {
-            pages.push(numeral_pages);
-        }
-        if pages.is_empty() {
-            return vec![Unpacked {
-                id: "0".to_string(),
-                head: "<not found>".to_string(),
-                ..Default::default()
-            }];
-        }
-
-        for id in pages.iter().flat_map(|p| p.clone()) {
-            let page = dict.pages.get_page(id).unwrap();
-            let parsed = parse_xml(page);
-            println!("{parsed:#?}");
-            unpacked.append(&mut unpack_dic_item(parsed))
-        }
     }
+    Ok(unpacked)
+}
 
-    unpacked
+#[cfg(any(feature = "python", test))]
+pub(crate) fn _look_up_entries(
+    path: &str,
+    vocab: &str,
+    word_type: Option<WordType>,
+) -> Result<Vec<DicItem>, Error> {
+    MonokakidoDict::open_with_path(path)?.look_up(vocab, word_type)
 }
 
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WordType {
     HEADWORD = 1,
@@ -114,26 +86,21 @@ pub enum WordType {
 }
 
 #[derive(Debug, Default)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 struct Unpacked {
-    #[pyo3(get)]
     id: String,
-    #[pyo3(get)]
     head: String,
-    #[pyo3(get)]
+    // kana headword, the number for counter forms
+    reading: String,
     kanji: Option<String>,
     // accent string, audio id
-    #[pyo3(get)]
     pron: Vec<Pron>,
 }
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all))]
 #[derive(Debug, Clone, Default, PartialEq)]
 struct Pron {
-    #[pyo3(get)]
     id: String,
-    #[pyo3(get)]
     accent: String,
-    #[pyo3(get)]
     sound_file: Option<String>,
 }
 
@@ -143,12 +110,18 @@ fn unpack_dic_item(dic_item: DicItem) -> Vec<Unpacked> {
     for head_g in dic_item.1 {
         let mut pron = Vec::new();
         let mut head = String::new();
+        let mut reading = String::new();
         let mut kanji = None;
 
-        // head, kanji
+        // head, reading, kanji
         if let Head::H(h) = head_g.0 {
             head = h.iter().map(|h| format!("{h} ")).collect();
             for i in h {
+                match &i {
+                    H::Headword(s) => reading.push_str(s),
+                    H::Subheadword(_, s) if reading.is_empty() => reading.push_str(s),
+                    _ => {}
+                }
                 if let H::HW(s, i) = i {
                     let mut s = s.chars();
                     s.next();
@@ -230,6 +203,7 @@ fn unpack_dic_item(dic_item: DicItem) -> Vec<Unpacked> {
             unpacked.push(Unpacked {
                 id: dic_item.0.0.clone(),
                 head,
+                reading,
                 kanji,
                 pron,
             })
@@ -271,6 +245,7 @@ fn unpack_numbers(numbers: &[Josushi]) -> Vec<Unpacked> {
         let unpa = Unpacked {
             id: id.clone(),
             head: head.clone(),
+            reading: head.clone(),
             kanji: None,
             pron: prons,
         };
@@ -288,3 +263,148 @@ fn get_sound_id(accent: &Accent) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{AME_SOUND, IPPON_SOUND, SAKURA_SOUND, build_dict, sound_data};
+
+    #[test]
+    fn look_up_by_reading() {
+        let dir = build_dict("look_up_reading");
+        let path = dir.to_str().unwrap();
+
+        let found = _look_up(path, "さくら", None).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, "00001");
+        assert_eq!(found[0].head, "さくら 【桜】 ");
+        assert_eq!(found[0].kanji.as_deref(), Some("桜"));
+        assert_eq!(
+            found[0].pron,
+            [Pron {
+                id: "0".to_string(),
+                accent: "サクラ▔".to_string(),
+                sound_file: Some(format!("{SAKURA_SOUND}.aac")),
+            }]
+        );
+
+        let found = _look_up(path, "アメ", None).unwrap();
+        assert_eq!(found[0].pron[0].accent, "ア＼メ");
+
+        let found = _look_up(path, "ゆき", None).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].head, "<not found>");
+    }
+
+    #[test]
+    fn look_up_full_entries() {
+        let dir = build_dict("look_up_entries");
+        let path = dir.to_str().unwrap();
+
+        let entries = _look_up_entries(path, "いっぽん", None).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = serde_json::to_value(&entries[0]).unwrap();
+        assert_eq!(entry["id"], "00003");
+        let head_g = &entry["head_groups"][0];
+        assert_eq!(head_g["head"]["H"][0]["Headword"], "―ほん");
+        assert_eq!(head_g["head"]["H"][1]["HW"]["text"], "【―本】");
+        let accent = &head_g["body"][0]["Accent"][0]["text"];
+        assert_eq!(accent[0]["Text"], "ホン");
+        assert_eq!(accent[1]["SymbolMacron"], "▔");
+        // josushi number with its anchor
+        let number = &entry["josushi"][0]["number"];
+        assert_eq!(number["id"], "00003-0001");
+        assert_eq!(number["text"], "1");
+
+        let item: DicItem = serde_json::from_value(entry.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&item).unwrap(), entry);
+
+        assert!(_look_up_entries(path, "ゆき", None).unwrap().is_empty());
+        assert_eq!(_look_up_entries(path, "2", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn look_up_by_page_index() {
+        let dir = build_dict("look_up_index");
+        let path = dir.to_str().unwrap();
+        // indexes are counted from one
+        let found = _look_up(path, "2", None).unwrap();
+        assert_eq!(found[0].id, "00002");
+        assert_eq!(found[0].kanji.as_deref(), Some("雨"));
+
+        // past the last page and too large for an index
+        for index in ["5", "99999", "99999999999999999999999"] {
+            let found = _look_up(path, index, None).unwrap();
+            assert_eq!(found[0].head, "<not found>");
+        }
+    }
+
+    #[test]
+    fn look_up_missing_page() {
+        let dir = build_dict("look_up_missing_page");
+        let mut writer = KeysWriter::new();
+        writer.add("サクラ", [PageItemId { page: 9, item: 0 }]);
+        writer
+            .write(dir.to_str().unwrap(), "headword.keyindex")
+            .unwrap();
+        let dict = MonokakidoDict::open_with_path(dir.to_str().unwrap()).unwrap();
+        assert_eq!(dict.look_up("サクラ", None).err(), Some(Error::NotFound));
+        assert_eq!(dict.look_up("ハナミ", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn look_up_by_word_type() {
+        let dir = build_dict("look_up_word_type");
+        let path = dir.to_str().unwrap();
+
+        let found = _look_up(path, "はなみ", Some(WordType::COMPOUND)).unwrap();
+        assert_eq!(found[0].kanji.as_deref(), Some("花見"));
+        let found = _look_up(path, "はなみ", Some(WordType::HEADWORD)).unwrap();
+        assert_eq!(found[0].head, "<not found>");
+
+        let found = _look_up(path, "いっぽん", Some(WordType::COUNTER)).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].id, "00003-0001");
+        assert_eq!(found[1].head, "1");
+        assert_eq!(found[1].pron[0].accent, "イッ＼ポン");
+        assert_eq!(
+            found[1].pron[0].sound_file,
+            Some(format!("{IPPON_SOUND}.aac"))
+        );
+    }
+
+    #[test]
+    fn audio() {
+        let dir = build_dict("audio");
+        let dict = MonokakidoDict::open_with_path(dir.to_str().unwrap()).unwrap();
+        for id in [SAKURA_SOUND, AME_SOUND, IPPON_SOUND] {
+            assert_eq!(*dict.audio.get(id).unwrap(), sound_data(id));
+        }
+        assert_eq!(dict.audio.get("1").err(), Some(Error::NotFound));
+        assert_eq!(dict.audio.idx_iter().unwrap().len(), 3);
+
+        let audio = dict
+            .pages
+            .get_item_audio(PageItemId { page: 2, item: 0 })
+            .unwrap();
+        assert_eq!(audio.len(), 1);
+        assert!(audio[0].contains(&format!("href=\"{AME_SOUND}.aac\"")));
+    }
+
+    #[test]
+    fn pitch_svg() {
+        let dir = build_dict("pitch_svg");
+        let found = _look_up(dir.to_str().unwrap(), "あめ", None).unwrap();
+        let svg = gen_svg::gen_svg(&found[0].pron[0].accent);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        // one text element per mora
+        assert_eq!(svg.matches("<text").count(), 2);
+    }
+
+    #[test]
+    fn headlines() {
+        let dir = build_dict("headlines");
+        assert!(Headlines::new(dir.to_str().unwrap()).is_ok());
+    }
+}
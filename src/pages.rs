@@ -1,12 +1,23 @@
-use std::{ops::Range, path::PathBuf};
-
-use crate::{Error, PageItemId, resource::Rsc};
+use std::{
+    borrow::Cow,
+    ops::Range,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    Error, LoadMode, PageItemId,
+    info::open_content,
+    resource::{CacheStats, Rsc},
+    storage::Storage,
+};
 
 const RSC_NAME: &str = "contents";
 
 pub struct Pages {
-    path: PathBuf,
-    res: Option<Rsc>,
+    storage: Arc<dyn Storage>,
+    cache_capacity: Option<usize>,
+    res: OnceLock<Rsc>,
 }
 
 pub struct XmlParser<'a> {
@@ -80,31 +91,56 @@ impl<'a> XmlParser<'a> {
 
 impl Pages {
     pub fn new(path: &str) -> Result<Self, Error> {
+        Self::with_mode(path, LoadMode::default())
+    }
+
+    pub fn with_mode(path: &str, mode: LoadMode) -> Result<Self, Error> {
+        Self::with_storage(open_content(Path::new(path), mode)?)
+    }
+
+    pub fn with_storage(storage: Arc<dyn Storage>) -> Result<Self, Error> {
         Ok(Pages {
-            path: PathBuf::from(path).join(RSC_NAME),
-            res: None,
+            storage,
+            cache_capacity: None,
+            res: OnceLock::new(),
         })
     }
 
-    pub fn init(&mut self) -> Result<(), Error> {
-        if self.res.is_none() {
-            self.res = Some(Rsc::new(&self.path, RSC_NAME)?);
+    pub fn init(&self) -> Result<(), Error> {
+        self.res().map(|_| ())
+    }
+
+    fn res(&self) -> Result<&Rsc, Error> {
+        if let Some(res) = self.res.get() {
+            return Ok(res);
+        }
+        let res = Rsc::new(&*self.storage, RSC_NAME, RSC_NAME)?;
+        if let Some(bytes) = self.cache_capacity {
+            res.set_cache_capacity(bytes);
         }
-        Ok(())
+        // another thread might have won the race, then its resource is used
+        Ok(self.res.get_or_init(|| res))
     }
 
-    pub fn get_page(&mut self, id: PageItemId) -> Result<&str, Error> {
-        self.init()?;
-        let Some(res) = self.res.as_mut() else {
-            unreachable!()
-        };
-        let xml = std::str::from_utf8(res.get(id.page)?).map_err(|_| Error::Utf8Error)?;
-        Ok(xml)
+    /// Upper bound in bytes for the decompressed content blocks kept in memory.
+    pub fn set_cache_capacity(&mut self, bytes: usize) {
+        self.cache_capacity = Some(bytes);
+        if let Some(res) = self.res.get() {
+            res.set_cache_capacity(bytes);
+        }
     }
 
-    pub fn get_item(&mut self, id: PageItemId) -> Result<&str, Error> {
+    pub fn cache_stats(&self) -> CacheStats {
+        self.res.get().map(Rsc::cache_stats).unwrap_or_default()
+    }
+
+    pub fn get_page(&self, id: PageItemId) -> Result<Cow<'_, str>, Error> {
+        to_str(self.res()?.get(id.page)?)
+    }
+
+    pub fn get_item(&self, id: PageItemId) -> Result<String, Error> {
         let xml = self.get_page(id)?;
-        let mut parser = XmlParser::from(xml);
+        let mut parser = XmlParser::from(&xml);
         if id.item == 0 {
             parser.next_fragment_by(|tag| tag == "body", |_, _| false)
         } else {
@@ -122,33 +158,48 @@ impl Pages {
                 },
             )
         }?
+        .map(str::to_owned)
         .ok_or(Error::XmlError)
     }
 
-    pub fn get_item_audio(&mut self, id: PageItemId) -> Result<AudioIter<'_>, Error> {
+    pub fn get_item_audio(&self, id: PageItemId) -> Result<Vec<String>, Error> {
         let xml = self.get_item(id)?;
-        let parser = XmlParser::from(xml);
-        Ok(AudioIter { parser })
+        let parser = XmlParser::from(&xml);
+        AudioIter { parser }
+            .map(|href| href.map(str::to_owned))
+            .collect()
     }
 
-    pub fn page_by_idx(&mut self, idx: usize) -> Result<(u32, &str), Error> {
-        self.init()?;
-        let Some(res) = self.res.as_mut() else {
-            unreachable!()
-        };
-        let (id, page) = res.get_by_idx(idx)?;
-        Ok((id, std::str::from_utf8(page).map_err(|_| Error::Utf8Error)?))
+    pub fn page_by_idx(&self, idx: usize) -> Result<(u32, Cow<'_, str>), Error> {
+        let (id, page) = self.res()?.get_by_idx(idx)?;
+        Ok((id, to_str(page)?))
     }
 
-    pub fn idx_iter(&mut self) -> Result<Range<usize>, Error> {
-        self.init()?;
-        let Some(res) = self.res.as_ref() else {
-            unreachable!()
-        };
-        Ok(0..res.len())
+    pub fn idx_iter(&self) -> Result<Range<usize>, Error> {
+        Ok(0..self.res()?.len())
     }
 }
 
+fn to_str(bytes: Cow<'_, [u8]>) -> Result<Cow<'_, str>, Error> {
+    Ok(match bytes {
+        Cow::Borrowed(bytes) => Cow::Borrowed(std::str::from_utf8(bytes)?),
+        Cow::Owned(bytes) => Cow::Owned(String::from_utf8(bytes).map_err(|_| Error::Utf8Error)?),
+    })
+}
+
+/// The `.aac` links of a page, in document order.
+pub(crate) fn audio_hrefs(xml: &str) -> impl Iterator<Item = Result<&str, Error>> {
+    xmlparser::Tokenizer::from(xml).filter_map(|token| match token {
+        Ok(xmlparser::Token::Attribute { local, value, .. })
+            if &*local == "href" && value.ends_with(".aac") =>
+        {
+            Some(Ok(value.as_str()))
+        }
+        Ok(_) => None,
+        Err(e) => Some(Err(e.into())),
+    })
+}
+
 pub struct AudioIter<'a> {
     parser: XmlParser<'a>,
 }
@@ -0,0 +1,175 @@
+use crate::gen_svg::{
+    CIRCLE, HALF_WIDTH_DAKUTEN, HALF_WIDTH_HANDAKUTEN, NAKATEN, VOICED, str_to_mora,
+};
+
+const DOWNSTEP: &str = "＼";
+const HEIBAN: &str = "▔";
+// the kana of ㋐ to ㋾, in code point order
+const CIRCLED_KANA: &str = "アイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワヰヱヲ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mora {
+    pub text: String,
+    pub high: bool,
+    pub devoiced: bool,
+    pub nasal: bool,
+}
+
+impl Mora {
+    /// The mora as plain hiragana: circles are dropped and a nasal mora is
+    /// written with the voiced kana it stands for, `ケ゚` becomes `げ`.
+    pub fn kana(&self) -> String {
+        let mut kana = String::new();
+        for c in self.text.chars() {
+            match c {
+                '㋐'..='㋾' => {
+                    let idx = (c as u32 - '㋐' as u32) as usize;
+                    kana.push(CIRCLED_KANA.chars().nth(idx).unwrap_or(c));
+                }
+                CIRCLE => {}
+                VOICED | HALF_WIDTH_DAKUTEN => add_dakuten(&mut kana, 1),
+                HALF_WIDTH_HANDAKUTEN => add_dakuten(&mut kana, 2),
+                c => kana.push(c),
+            }
+        }
+        kana.chars().map(to_hiragana).collect()
+    }
+}
+
+/// Replaces the last kana of the か to ほ rows by its voiced (`offset` 1) or
+/// half voiced (`offset` 2) form.
+fn add_dakuten(kana: &mut String, offset: u32) {
+    let Some(c) = kana.pop() else { return };
+    let voiced = matches!(c, 'か'..='ほ' | 'カ'..='ホ')
+        .then(|| char::from_u32(c as u32 + offset))
+        .flatten();
+    kana.push(voiced.unwrap_or(c));
+}
+
+fn to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' => char::from_u32(c as u32 - ('ア' as u32 - 'あ' as u32)).unwrap_or(c),
+        c => c,
+    }
+}
+
+/// One word of an accent string, words are separated by `・`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PitchWord {
+    pub morae: Vec<Mora>,
+    /// Number of morae before the pitch drop, `None` for heiban.
+    pub downstep: Option<usize>,
+}
+
+/// Splits an accent string like `ジュ＼ー・ゴ＼ニチ` into words and assigns
+/// high/low pitch to every mora. Characters that are not part of the reading
+/// (brackets, kanji, ...) are dropped.
+pub fn parse_pitch(accent: &str) -> Vec<PitchWord> {
+    str_to_mora(accent.trim())
+        .split(|m| m.starts_with(NAKATEN))
+        .map(parse_word)
+        .filter(|w| !w.morae.is_empty())
+        .collect()
+}
+
+fn parse_word(mora: &[String]) -> PitchWord {
+    let mut texts = Vec::new();
+    let mut downstep = None;
+    for m in mora {
+        if m == DOWNSTEP {
+            downstep = Some(texts.len());
+        } else if m == HEIBAN {
+            downstep = None;
+        } else if m.starts_with(is_mora_char) {
+            texts.push(m.as_str());
+        }
+    }
+    // a drop before the first mora carries no information
+    let downstep = downstep.filter(|d| *d > 0);
+
+    let morae = texts
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let high = match downstep {
+                Some(1) => i == 0,
+                Some(d) => i > 0 && i < d,
+                None => i > 0,
+            };
+            Mora {
+                text: text.to_string(),
+                high,
+                devoiced: text.starts_with(is_circled) || text.contains(CIRCLE),
+                nasal: text.contains(VOICED),
+            }
+        })
+        .collect();
+    PitchWord { morae, downstep }
+}
+
+fn is_circled(c: char) -> bool {
+    ('㋐'..='㋾').contains(&c)
+}
+
+fn is_mora_char(c: char) -> bool {
+    ('ぁ'..='ゖ').contains(&c) || ('ァ'..='ヺ').contains(&c) || c == 'ー' || is_circled(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highs(word: &PitchWord) -> Vec<bool> {
+        word.morae.iter().map(|m| m.high).collect()
+    }
+
+    #[test]
+    fn patterns() {
+        let words = parse_pitch("ジュ＼ー・ゴ＼ニチ");
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].downstep, Some(1));
+        assert_eq!(highs(&words[0]), vec![true, false]);
+        assert_eq!(words[1].downstep, Some(1));
+        assert_eq!(highs(&words[1]), vec![true, false, false]);
+
+        let word = &parse_pitch("アブラ＼ケ゚")[0];
+        assert_eq!(word.downstep, Some(3));
+        assert_eq!(highs(word), vec![false, true, true, false]);
+        assert!(word.morae[3].nasal);
+
+        let word = &parse_pitch("サクラ▔")[0];
+        assert_eq!(word.downstep, None);
+        assert_eq!(highs(word), vec![false, true, true]);
+    }
+
+    #[test]
+    fn devoiced() {
+        let word = &parse_pitch("㋖ク")[0];
+        assert!(word.morae[0].devoiced);
+        assert!(!word.morae[1].devoiced);
+        let word = &parse_pitch("き⃝く")[0];
+        assert!(word.morae[0].devoiced);
+    }
+
+    #[test]
+    fn kana() {
+        let kana = |accent| -> Vec<String> {
+            parse_pitch(accent)[0]
+                .morae
+                .iter()
+                .map(Mora::kana)
+                .collect()
+        };
+        assert_eq!(kana("アブラ＼ケ゚"), ["あ", "ぶ", "ら", "げ"]);
+        assert_eq!(kana("㋖ョク"), ["きょ", "く"]);
+        assert_eq!(kana("き⃝く"), ["き", "く"]);
+        assert_eq!(kana("㋪ﾟカﾞー"), ["ぴ", "が", "ー"]);
+    }
+
+    #[test]
+    fn skip_non_reading() {
+        let word = &parse_pitch("（「オ㋛フィエ＼ンチム」）")[0];
+        let text: String = word.morae.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(text, "オ㋛フィエンチム");
+    }
+}
@@ -1,6 +1,6 @@
 use super::*;
 use nom::{
-    IResult, Parser,
+    Parser,
     branch::alt,
     character::complete::char,
     combinator::opt,
@@ -9,8 +9,9 @@ use nom::{
 };
 
 pub fn parse_example(input: &str) -> IResult<&str, Example> {
-    xml_tag(
+    xml_class(
         "span",
+        "example",
         (
             parse_example_head,
             many1(preceded(
@@ -24,16 +25,20 @@ pub fn parse_example(input: &str) -> IResult<&str, Example> {
         )
             .map(|(h, a)| Example(h, a)),
     )(input)
-    .verify_class("example")
 }
 
 fn parse_example_head(input: &str) -> IResult<&str, ExampleHead> {
-    xml_tag("span", parse_named_word.map(|(n, w)| ExampleHead(n, w)))(input).verify_class("ex_head")
+    xml_class(
+        "span",
+        "ex_head",
+        parse_named_word.map(|(n, w)| ExampleHead(n, w)),
+    )(input)
 }
 
 fn parse_accent_example(input: &str) -> IResult<&str, Vec<AccentText>> {
-    xml_tag(
+    xml_class(
         "span",
+        "accent accent_example",
         many0(alt((
             parse_symbol_macron.map(|x| AccentText::SymbolMacron(x.to_string())),
             parse_symbol_backslash.map(|x| AccentText::SymbolBackslash(x.to_string())),
@@ -42,5 +47,4 @@ fn parse_accent_example(input: &str) -> IResult<&str, Vec<AccentText>> {
             text.map(|x| AccentText::Text(x.to_string())),
         ))),
     )(input)
-    .verify_class("accent accent_example")
 }
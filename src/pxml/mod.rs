@@ -2,121 +2,196 @@ mod example;
 mod josushi;
 mod ruby;
 mod structs;
+mod unknown;
 mod xml;
+use std::fmt;
+
 use example::*;
 use josushi::*;
 use nom::{
-    IResult, Parser,
+    Parser,
     branch::alt,
     bytes::complete::take_until,
     character::complete::char,
     combinator::opt,
-    error::{ErrorKind, ParseError},
     multi::{many0, many1},
     sequence::preceded,
 };
-pub use ruby::*;
+pub(crate) use ruby::*;
 pub use structs::*;
-pub use xml::*;
+use unknown::parse_unknown;
+pub(crate) use xml::*;
+
+const PROLOG: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+// chars of the page shown in `ParseError::context`
+const CONTEXT_CHARS: usize = 40;
+
+/// Why a page could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset into the page, the xml declaration included.
+    pub offset: usize,
+    /// The element, class or attribute the grammar expected at `offset`.
+    pub expected: String,
+    /// The page text starting at `offset`, cut after a few chars.
+    pub context: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} at byte {}: {:?}",
+            self.expected, self.offset, self.context
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    fn new(page: &str, rem: &str, expected: Expected) -> Self {
+        let offset = page.len() - rem.len();
+        ParseError {
+            offset,
+            expected: expected.to_string(),
+            context: rem.chars().take(CONTEXT_CHARS).collect(),
+        }
+    }
+}
+
+/// Whether markup the grammar has no rule for fails the page, or is kept as
+/// `Unknown` where the grammar allows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Strict,
+    Lenient,
+}
 
+/// Parses a contents page.
+pub fn try_parse_xml(xml: &str) -> Result<DicItem, ParseError> {
+    parse_page(xml, Mode::Strict)
+}
+
+/// Like `try_parse_xml`, but spans and divs of classes the grammar doesn't know
+/// are kept as `Unknown` where body content, headword parts or accent text are
+/// expected, instead of failing the whole page.
+pub fn try_parse_xml_lenient(xml: &str) -> Result<DicItem, ParseError> {
+    parse_page(xml, Mode::Lenient)
+}
+
+fn parse_page(xml: &str, mode: Mode) -> Result<DicItem, ParseError> {
+    let Some(html) = xml.strip_prefix(PROLOG) else {
+        return Err(ParseError::new(xml, xml, Expected::Prolog));
+    };
+    let (rem, dic_item) = match parse_html(mode)(html) {
+        Ok(parsed) => parsed,
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+            return Err(ParseError::new(xml, e.input, e.expected));
+        }
+        Err(nom::Err::Incomplete(_)) => unreachable!("only complete parsers are used"),
+    };
+    let rem = rem.trim_start();
+    if !rem.is_empty() {
+        return Err(ParseError::new(xml, rem, Expected::End));
+    }
+    Ok(dic_item)
+}
+
+/// Parses a contents page. Panics if it doesn't match the grammar, see `try_parse_xml`.
 pub fn parse_xml(xml: &str) -> DicItem {
-    let xml = xml
-        .strip_prefix(
-            "<?xml version=\"1.0\" encoding=\"utf-8\"?>
-",
-        )
-        .unwrap();
-    let (rem, dic_item) = parse_html(xml).unwrap_or_else(|_| panic!("failed to parse\n{xml}\n"));
-    assert!(rem.trim().is_empty());
-    dic_item
+    try_parse_xml(xml).unwrap_or_else(|e| panic!("failed to parse page: {e}"))
 }
 
-fn parse_html(input: &str) -> IResult<&str, DicItem> {
-    xml_tag("html", preceded(parse_head, parse_body))(input).map(|(rem, (_, d))| (rem, d))
+fn parse_html<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, DicItem> {
+    let mut parser = xml_tag("html", preceded(parse_head, parse_body(mode)));
+    move |input| parser(input).map(|(rem, (_, d))| (rem, d))
 }
 
 fn parse_head(input: &str) -> IResult<&str, ()> {
     xml_tag("head", take_until("</head>"))(input).map(|(rem, _)| (rem, ()))
 }
 
-fn parse_body(input: &str) -> IResult<&str, DicItem> {
-    xml_tag("body", parse_dic_item)(input).map(|(rem, (_, d))| (rem, d))
+fn parse_body<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, DicItem> {
+    let mut parser = xml_tag("body", parse_dic_item(mode));
+    move |input| parser(input).map(|(rem, (_, d))| (rem, d))
 }
 
-fn parse_dic_item(input: &str) -> IResult<&str, DicItem> {
-    xml_tag("span", (many1(parse_head_g), many0(parse_josuhi)))(input).and_then(
-        |(rem, (attr, (i, j)))| {
-            if attr.attr("class") == Some("dic-item") {
-                let id = Id(attr.attr("id").unwrap().parse().unwrap());
-
-                Ok((rem, DicItem(id, i, j)))
-            } else {
-                Err(nom::Err::Error(ParseError::from_error_kind(
-                    input,
-                    ErrorKind::Satisfy,
-                )))
-            }
-        },
-    )
+fn parse_dic_item<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, DicItem> {
+    let mut parser = xml_class_tag(
+        "span",
+        "dic-item",
+        (many1(parse_head_g(mode)), many0(parse_josuhi(mode))),
+    );
+    move |input| {
+        let (rem, (attrs, (i, j))) = parser(input)?;
+        let Some(id) = attrs.attr("id") else {
+            return fail(input, Expected::HasAttr("id"));
+        };
+        Ok((rem, DicItem(Id(id.into()), i, j)))
+    }
 }
 
-fn parse_head_g(input: &str) -> IResult<&str, HeadG> {
-    xml_tag(
+fn parse_head_g<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, HeadG> {
+    xml_class(
         "span",
+        "head-g",
         (
-            alt((parse_dic_head, parse_dic_head_empty)),
-            alt((parse_dic_body, parse_dic_body_empty)),
+            alt((parse_dic_head(mode), parse_dic_head_empty)),
+            alt((parse_dic_body(mode), parse_dic_body_empty)),
         )
             .map(|(h, b)| HeadG(h, b)),
-    )(input)
-    .verify_class("head-g")
+    )
 }
 
-fn parse_dic_head(input: &str) -> IResult<&str, Head> {
-    xml_tag(
+fn parse_dic_head<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, Head> {
+    xml_class(
         "div",
+        "head",
         alt((
-            parse_h.map(Head::H),
+            parse_h(mode).map(Head::H),
             parse_joshi_word.map(Head::Joshiword),
             parse_refhead.map(Head::Ref),
         )),
-    )(input)
-    .verify_class("head")
+    )
 }
 
 fn parse_dic_head_empty(input: &str) -> IResult<&str, Head> {
-    empty_xml_tag("div", input)
-        .verify_class("head")
-        .map(|(rem, _res)| (rem, Head::None))
+    empty_xml_class("div", "head", input).map(|(rem, _)| (rem, Head::None))
 }
 
 fn parse_dic_body_empty(input: &str) -> IResult<&str, Body> {
-    empty_xml_tag("div", input)
-        .verify_class("body")
-        .map(|(rem, _res)| (rem, Body(Vec::new())))
+    empty_xml_class("div", "body", input).map(|(rem, _)| (rem, Body(Vec::new())))
 }
 
 fn parse_joshi_word(input: &str) -> IResult<&str, Joshiword> {
-    xml_tag("span", parse_named_word.map(|(n, w)| Joshiword(n, w)))(input).verify_class("joshiword")
+    xml_class(
+        "span",
+        "joshiword",
+        parse_named_word.map(|(n, w)| Joshiword(n, w)),
+    )(input)
 }
 
 fn parse_subheadword(input: &str) -> IResult<&str, H> {
-    xml_tag("span", parse_named_word.map(|(n, w)| H::Subheadword(n, w)))(input)
-        .verify_class("subheadword ")
+    xml_class(
+        "span",
+        "subheadword ",
+        parse_named_word.map(|(n, w)| H::Subheadword(n, w)),
+    )(input)
 }
 
 fn parse_named_word(input: &str) -> IResult<&str, (ID, String)> {
-    xml_tag("a", text)(input)
-        .map(|(rem, (attrs, x))| {
-            let name = ID(attrs.attr("name").unwrap().into());
-            (rem, (attrs, (name, x.into())))
-        })
-        .verify_class("anchor")
+    let (rem, (attrs, x)) = xml_class_tag("a", "anchor", text)(input)?;
+    let Some(name) = attrs.attr("name") else {
+        return fail(input, Expected::HasAttr("name"));
+    };
+    Ok((rem, (ID(name.into()), x.into())))
 }
 
-fn parse_h(input: &str) -> IResult<&str, Vec<H>> {
-    xml_tag(
+fn parse_h<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, Vec<H>> {
+    xml_class(
         "span",
+        "h",
         many0(alt((
             parse_headword.map(|x| H::Headword(x.into())),
             parse_hw.map(|(hw, o)| H::HW(hw.into(), o)),
@@ -128,18 +203,19 @@ fn parse_h(input: &str) -> IResult<&str, Vec<H>> {
             parse_d_angle_brackets.map(|s| H::DAngleBrackets(s.into())),
             parse_angle_brackets.map(|s| H::AngleBrackets(s.into())),
             parse_dia.map(|s| H::Dia(s.into())),
+            parse_unknown(mode).map(H::Unknown),
         ))),
-    )(input)
-    .verify_class("h")
+    )
 }
 
 fn parse_headword(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("headword")
+    xml_class("span", "headword", text)(input)
 }
 
 fn parse_hw(input: &str) -> IResult<&str, (&str, Option<(Vec<Inner>, char)>)> {
-    xml_tag(
+    xml_class(
         "span",
+        "hw",
         (
             text,
             opt((
@@ -154,11 +230,11 @@ fn parse_hw(input: &str) -> IResult<&str, (&str, Option<(Vec<Inner>, char)>)> {
             )),
         ),
     )(input)
-    .verify_class("hw")
 }
 fn parse_black_branckets(input: &str) -> IResult<&str, (&str, Option<(Inner, char)>)> {
-    xml_tag(
+    xml_class(
         "span",
+        "black_branckets",
         (
             text,
             opt((
@@ -171,7 +247,6 @@ fn parse_black_branckets(input: &str) -> IResult<&str, (&str, Option<(Inner, cha
             )),
         ),
     )(input)
-    .verify_class("black_branckets")
 }
 
 fn parse_span(input: &str) -> IResult<&str, &str> {
@@ -179,41 +254,39 @@ fn parse_span(input: &str) -> IResult<&str, &str> {
         if attr.is_empty() {
             Ok((rem, t))
         } else {
-            Err(nom::Err::Error(ParseError::from_error_kind(
-                input,
-                ErrorKind::Tag,
-            )))
+            fail(input, Expected::Tag("span"))
         }
     })
 }
 
 fn parse_d_angle_brackets(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("d_angle_brackets")
+    xml_class("span", "d_angle_brackets", text)(input)
 }
 
 fn parse_angle_brackets(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("angle_brackets")
+    xml_class("span", "angle_brackets", text)(input)
 }
 
 fn parse_dia(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("dia")
+    xml_class("span", "dia", text)(input)
 }
 
 fn parse_square_brackets(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("square_brackets")
+    xml_class("span", "square_brackets", text)(input)
 }
 
 fn parse_square_box(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("square_box")
+    xml_class("span", "square_box", text)(input)
 }
 
 fn parse_round_brackets(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("round_brackets")
+    xml_class("span", "round_brackets", text)(input)
 }
 
 fn parse_refhead(input: &str) -> IResult<&str, Vec<RefHead>> {
-    xml_tag(
+    xml_class(
         "span",
+        "ref",
         many1(alt((
             parse_refheadword.map(|s| RefHead::Refheadword(s.into())),
             parse_black_branckets.map(|(b, d)| RefHead::BlackBranckets(b.into(), d)),
@@ -222,42 +295,45 @@ fn parse_refhead(input: &str) -> IResult<&str, Vec<RefHead>> {
             parse_square_brackets.map(|s| RefHead::SquareBrackets(s.into())),
         ))),
     )(input)
-    .verify_class("ref")
 }
 fn parse_refheadword(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("refheadword")
+    xml_class("span", "refheadword", text)(input)
 }
 
-fn parse_dic_body(input: &str) -> IResult<&str, Body> {
-    xml_tag(
+fn parse_dic_body<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, Body> {
+    xml_class(
         "div",
+        "body",
         many1(alt((
-            parse_con_table.map(BodyContent::ConTable),
-            many1(parse_accent).map(BodyContent::Accent),
+            parse_con_table(mode).map(BodyContent::ConTable),
+            many1(parse_accent(mode)).map(BodyContent::Accent),
             parse_bodyref.map(|(i, c)| BodyContent::Ref(i, c)),
             parse_accent_round.map(|(r, a)| BodyContent::AccentRound(r, a)),
             parse_square_box.map(|s| BodyContent::SquareBox(s.into())),
             parse_example.map(BodyContent::Example),
-        ))),
-    )(input)
-    .verify_class("body")
-    .map(|(rem, c)| (rem, Body(c)))
+            parse_unknown(mode).map(BodyContent::Unknown),
+        )))
+        .map(Body),
+    )
 }
 
-fn parse_con_table(input: &str) -> IResult<&str, Vec<ConTableContent>> {
-    xml_tag(
+fn parse_con_table<'s>(
+    mode: Mode,
+) -> impl FnMut(&'s str) -> IResult<&'s str, Vec<ConTableContent>> {
+    xml_class(
         "span",
+        "con_table",
         many1(alt((
-            many1(alt((parse_accent, parse_accent2))).map(ConTableContent::Accent),
+            many1(alt((parse_accent(mode), parse_accent2))).map(ConTableContent::Accent),
             parse_accent_round.map(|(r, a)| ConTableContent::AccentRound(r, a)),
         ))),
-    )(input)
-    .verify_class("con_table")
+    )
 }
 
 fn parse_accent2(input: &str) -> IResult<&str, Accent> {
-    xml_tag(
+    xml_class(
         "span",
+        "accent",
         many0(alt((
             parse_symbol_macron.map(|x| AccentText::SymbolMacron(x.to_string())),
             parse_symbol_backslash.map(|x| AccentText::SymbolBackslash(x.to_string())),
@@ -266,7 +342,6 @@ fn parse_accent2(input: &str) -> IResult<&str, Accent> {
             text.map(|x| AccentText::Text(x.to_string())),
         ))),
     )(input)
-    .verify_class("accent")
     .map(|(rem, a)| (rem, Accent(None, a)))
 }
 
@@ -279,40 +354,40 @@ fn parse_accent_head(input: &str) -> IResult<&str, AccentHead> {
 }
 
 fn parse_bodyref(input: &str) -> IResult<&str, (Id, Vec<RefContent>)> {
-    xml_tag("span", parse_ref)(input).verify_class("ref")
+    xml_class("span", "ref", parse_ref)(input)
 }
 
 fn parse_ref(input: &str) -> IResult<&str, (Id, Vec<RefContent>)> {
-    xml_tag(
+    let (rem, (attrs, c)) = xml_tag(
         "a",
         many1(alt((
             parse_round_brackets.map(|s| RefContent::RoundBrackets(s.into())),
             parse_text.map(|s| RefContent::TextSpan(s.into())),
             ref_text.map(|s| RefContent::Text(s.into())),
         ))),
-    )(input)
-    .map(|(rem, (attrs, c))| {
-        let id = Id(attrs.attr("href").unwrap().into());
-
-        (rem, (id, c))
-    })
+    )(input)?;
+    let Some(href) = attrs.attr("href") else {
+        return fail(input, Expected::HasAttr("href"));
+    };
+    Ok((rem, (Id(href.into()), c)))
 }
 
 fn parse_text(input: &str) -> IResult<&str, &str> {
     xml_tag("text", text)(input).map(|(rem, (_attr, t))| (rem, t))
 }
 
-fn parse_accent(input: &str) -> IResult<&str, Accent> {
-    xml_tag(
+fn parse_accent<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, Accent> {
+    xml_class(
         "span",
-        (opt(parse_accent_head), parse_accent_text).map(|(h, a)| Accent(h, a)),
-    )(input)
-    .verify_class("accent")
+        "accent",
+        (opt(parse_accent_head), parse_accent_text(mode)).map(|(h, a)| Accent(h, a)),
+    )
 }
 
-fn parse_accent_text(input: &str) -> IResult<&str, Vec<AccentText>> {
-    xml_tag(
+fn parse_accent_text<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, Vec<AccentText>> {
+    xml_class(
         "span",
+        "accent_text",
         many0(alt((
             parse_symbol_macron.map(|x| AccentText::SymbolMacron(x.to_string())),
             parse_symbol_backslash.map(|x| AccentText::SymbolBackslash(x.to_string())),
@@ -321,18 +396,19 @@ fn parse_accent_text(input: &str) -> IResult<&str, Vec<AccentText>> {
             text.map(|x| AccentText::Text(x.to_string())),
             parse_square_box.map(|s| AccentText::SquareBox(s.into())),
             parse_note_ref.map(|s| AccentText::NoteRef(s.into())),
+            parse_unknown(mode).map(AccentText::Unknown),
         ))),
-    )(input)
-    .verify_class("accent_text")
+    )
 }
 
 fn parse_note_ref(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("note_ref")
+    xml_class("span", "note_ref", text)(input)
 }
 
 fn parse_round_brackets2(input: &str) -> IResult<&str, RoundBrackets> {
-    xml_tag(
+    xml_class(
         "span",
+        "round_brackets",
         many1(alt((
             parse_symbol_macron.map(|x| AccentText::SymbolMacron(x.to_string())),
             parse_symbol_backslash.map(|x| AccentText::SymbolBackslash(x.to_string())),
@@ -342,37 +418,46 @@ fn parse_round_brackets2(input: &str) -> IResult<&str, RoundBrackets> {
         )))
         .map(RoundBrackets),
     )(input)
-    .verify_class("round_brackets")
 }
 
 fn parse_accent_round(input: &str) -> IResult<&str, (RoundBrackets, Option<Audio>)> {
-    xml_tag("span", (parse_round_brackets2, opt(parse_sound.map(Audio))))(input)
-        .verify_class("accent accent_round")
+    xml_class(
+        "span",
+        "accent accent_round",
+        (parse_round_brackets2, opt(parse_sound.map(Audio))),
+    )(input)
 }
 
 fn parse_symbol_macron(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("symbol_macron")
+    xml_class("span", "symbol_macron", text)(input)
 }
 
 fn parse_symbol_backslash(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("symbol_backslash")
+    xml_class("span", "symbol_backslash", text)(input)
 }
 
 fn parse_round_box(input: &str) -> IResult<&str, &str> {
-    xml_tag("span", text)(input).verify_class("round_box")
+    xml_class("span", "round_box", text)(input)
 }
 
 fn parse_sound(input: &str) -> IResult<&str, String> {
-    xml_tag("span", parse_sound_link)(input).verify_class("sound")
+    xml_class("span", "sound", parse_sound_link)(input)
 }
 
 fn parse_sound_link(input: &str) -> IResult<&str, String> {
-    xml_tag("a", parse_img)(input)
-        .map(|(rem, (attr, _))| (rem, attr.attr("href").unwrap().to_owned()))
+    let (rem, (attrs, _)) = xml_tag("a", parse_img)(input)?;
+    let Some(href) = attrs.attr("href") else {
+        return fail(input, Expected::HasAttr("href"));
+    };
+    Ok((rem, href.to_owned()))
 }
 
 fn parse_img(input: &str) -> IResult<&str, ()> {
-    empty_xml_tag("img", input).verify("alt", "音声")
+    let (rem, attrs) = empty_xml_tag("img", input)?;
+    if attrs.attr("alt") != Some("音声") {
+        return fail(input, Expected::Attr("alt", "音声"));
+    }
+    Ok((rem, ()))
 }
 
 #[cfg(test)]
@@ -392,6 +477,117 @@ mod tests {
         assert_eq!(sound, "20170630130152.aac")
     }
 
+    fn page(head_g: &str) -> String {
+        format!(
+            "{PROLOG}<html><head></head><body><span class=\"dic-item\" id=\"00007\">{head_g}</span></body></html>\n"
+        )
+    }
+
+    const HEAD_G: &str = "<span class=\"head-g\"><div class=\"head\"><span class=\"h\"><span class=\"headword\">あめ</span></span></div><div class=\"body\"><span class=\"accent\"><span class=\"accent_text\">ア<span class=\"symbol_backslash\">＼</span>メ</span></span></div></span>";
+
+    #[test]
+    fn try_parse_page() {
+        let DicItem(Id(id), head_gs, josushi) = try_parse_xml(&page(HEAD_G)).unwrap();
+        assert_eq!(id, "00007");
+        assert_eq!(head_gs.len(), 1);
+        assert!(josushi.is_empty());
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let broken = HEAD_G.replace("symbol_backslash", "symbol_slash");
+        let xml = page(&broken);
+        let err = try_parse_xml(&xml).unwrap_err();
+        let at = xml.find("<span class=\"symbol_slash\"").unwrap();
+        assert_eq!(err.offset, at);
+        assert!(xml[at..].starts_with(&err.context));
+        assert_eq!(err.context.chars().count(), CONTEXT_CHARS);
+        assert_eq!(err.expected, "</span>");
+    }
+
+    #[test]
+    fn parse_error_attr() {
+        let xml = page(HEAD_G).replace(" id=\"00007\"", "");
+        let err = try_parse_xml(&xml).unwrap_err();
+        assert_eq!(err.offset, xml.find("<span class=\"dic-item\"").unwrap());
+        assert_eq!(err.expected, "id attribute");
+    }
+
+    #[test]
+    fn parse_error_prolog_and_end() {
+        let xml = page(HEAD_G);
+        let err = try_parse_xml(&xml[1..]).unwrap_err();
+        assert_eq!(err.offset, 0);
+        assert_eq!(err.expected, "xml declaration");
+
+        let xml = format!("{xml}<p/>");
+        let err = try_parse_xml(&xml).unwrap_err();
+        assert_eq!(err.offset, xml.len() - 4);
+        assert_eq!(err.expected, "end of page");
+        assert_eq!(err.context, "<p/>");
+    }
+
+    #[test]
+    fn lenient_unknown_markup() {
+        let new_markup =
+            "<span class=\"pitch_note\" data-n=\"2\">中<span class=\"em\">高</span>型<br/></span>";
+        let head_g = HEAD_G
+            .replace(
+                "</span></div><div",
+                &format!("{new_markup}</span></div><div"),
+            )
+            .replace(
+                "</span></span></div></span>",
+                &format!("</span></span>{new_markup}</div></span>"),
+            );
+        let xml = page(&head_g);
+        assert!(try_parse_xml(&xml).is_err());
+
+        let DicItem(_, head_gs, _) = try_parse_xml_lenient(&xml).unwrap();
+        let HeadG(Head::H(h), Body(body)) = &head_gs[0] else {
+            panic!("no headword");
+        };
+        let [H::Headword(word), H::Unknown(unknown)] = &h[..] else {
+            panic!("{h:?}");
+        };
+        assert_eq!(word, "あめ");
+        assert_eq!(unknown.tag, "span");
+        assert_eq!(unknown.class.as_deref(), Some("pitch_note"));
+        assert_eq!(unknown.attrs, [("data-n".to_string(), "2".to_string())]);
+        assert_eq!(unknown.text, "中高型");
+        assert_eq!(unknown.children.len(), 2);
+        assert_eq!(unknown.children[0].class.as_deref(), Some("em"));
+        assert_eq!(unknown.children[1].tag, "br");
+
+        let [BodyContent::Accent(accent), BodyContent::Unknown(unknown)] = &body[..] else {
+            panic!("{body:?}");
+        };
+        assert_eq!(accent[0].to_string(), "ア＼メ");
+        assert_eq!(unknown.text, "中高型");
+    }
+
+    #[test]
+    fn lenient_unknown_child() {
+        let note = "<span class=\"pitch_note\">中高</span>";
+        let head_g = HEAD_G.replace("メ</span>", &format!("メ{note}</span>"));
+        let xml = page(&head_g);
+        let err = try_parse_xml(&xml).unwrap_err();
+        assert_eq!(err.offset, xml.find(note).unwrap());
+
+        // only the unknown child is kept as such, the accent around it parses
+        let DicItem(_, head_gs, _) = try_parse_xml_lenient(&xml).unwrap();
+        let HeadG(_, Body(body)) = &head_gs[0];
+        let [BodyContent::Accent(accent)] = &body[..] else {
+            panic!("{body:?}");
+        };
+        assert_eq!(accent[0].to_string(), "ア＼メ");
+        let Some(AccentText::Unknown(unknown)) = accent[0].1.last() else {
+            panic!("{accent:?}");
+        };
+        assert_eq!(unknown.class.as_deref(), Some("pitch_note"));
+        assert_eq!(unknown.text, "中高");
+    }
+
     #[test]
     fn symbol_macron() {
         let s = "<span class=\"symbol_macron\">▔</span>";
@@ -401,13 +597,13 @@ mod tests {
     #[test]
     fn accent() {
         let s = "<span class=\"accent_text\"><span class=\"symbol_macron\">▔</span><span class=\"sound\"><a href=\"20170630130152.aac\"><img alt=\"音声\" src=\"HMDicAudio.png\"/></a></span></span>";
-        let (rem, acc) = parse_accent_text(s).unwrap();
+        let (rem, acc) = parse_accent_text(Mode::Strict)(s).unwrap();
     }
 
     #[test]
     fn accent2() {
         let s = "<span class=\"accent_text\"><span class=\"symbol_macron\">▔</span><span class=\"sound\"><a href=\"20170630130152.aac\"><img alt=\"音声\" src=\"HMDicAudio.png\"/></a></span></span>";
-        let (rem, acc) = parse_accent_text(s).unwrap();
+        let (rem, acc) = parse_accent_text(Mode::Strict)(s).unwrap();
     }
 
     #[test]
@@ -424,12 +620,12 @@ mod tests {
     #[test]
     fn head_g_2() {
         let s = "<span class=\"head-g\"><div class=\"head\"><span class=\"h\"><span class=\"subheadword \"><a name=\"01611-0001\" class=\"anchor\">あぶらげ</a></span><span class=\"d_angle_brackets\">《×油揚》</span></span></div><div class=\"body\"><span class=\"accent\"><span class=\"accent_text\">アブラ<span class=\"symbol_backslash\">＼</span>ケ゚<span class=\"sound\"><a href=\"20170714114529.aac\"><img alt=\"音声\" src=\"HMDicAudio.png\"/></a></span></span></span></div></span></span>";
-        let (rem, res) = parse_head_g(s).unwrap();
+        let (rem, res) = parse_head_g(Mode::Strict)(s).unwrap();
     }
     #[test]
     fn head_2() {
         let s = "<div class=\"head\"><span class=\"h\"><span class=\"subheadword \"><a name=\"01611-0001\" class=\"anchor\">あぶらげ</a></span><span class=\"d_angle_brackets\">《×油揚》</span></span></div>";
-        let (rem, res) = parse_dic_head(s).unwrap();
+        let (rem, res) = parse_dic_head(Mode::Strict)(s).unwrap();
     }
     #[test]
     fn hw_2() {
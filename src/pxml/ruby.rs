@@ -1,5 +1,3 @@
-use nom::IResult;
-
 use super::*;
 
 fn parse_rb(input: &str) -> IResult<&str, Rb> {
@@ -1,14 +1,74 @@
 use std::fmt;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, ser::SerializeStruct};
 
 use crate::circle::to_circle;
-#[derive(Debug, Serialize, Deserialize)]
+
+/// Serde for a tuple struct as a map with the field names, so the JSON of an
+/// entry, and the dicts Python gets, don't depend on the order of the fields.
+macro_rules! named_fields {
+    ($name:ident { $($idx:tt: $field:ident: $ty:ty),+ $(,)? }) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let len = [$(stringify!($field)),+].len();
+                let mut s = serializer.serialize_struct(stringify!($name), len)?;
+                $(s.serialize_field(stringify!($field), &self.$idx)?;)+
+                s.end()
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                #[derive(Deserialize)]
+                struct Named {
+                    $($field: $ty),+
+                }
+                let Named { $($field),+ } = Named::deserialize(deserializer)?;
+                Ok($name($($field),+))
+            }
+        }
+    };
+}
+
+/// The same for enum variants with several fields, used as
+/// `#[serde(with = "module")]`.
+macro_rules! named_variant {
+    ($module:ident($($field:ident: $ty:ty),+ $(,)?)) => {
+        mod $module {
+            use super::*;
+
+            pub(super) fn serialize<S: Serializer>(
+                $($field: &$ty,)+
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                let len = [$(stringify!($field)),+].len();
+                let mut s = serializer.serialize_struct(stringify!($module), len)?;
+                $(s.serialize_field(stringify!($field), $field)?;)+
+                s.end()
+            }
+
+            pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<($($ty),+), D::Error> {
+                #[derive(Deserialize)]
+                struct Named {
+                    $($field: $ty),+
+                }
+                let Named { $($field),+ } = Named::deserialize(deserializer)?;
+                Ok(($($field),+))
+            }
+        }
+    };
+}
+
+#[derive(Debug)]
 pub struct DicItem(pub Id, pub Vec<HeadG>, pub Vec<Josushi>);
+named_fields!(DicItem { 0: id: Id, 1: head_groups: Vec<HeadG>, 2: josushi: Vec<Josushi> });
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Id(pub String);
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct HeadG(pub Head, pub Body);
+named_fields!(HeadG { 0: head: Head, 1: body: Body });
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Head {
     H(Vec<H>),
@@ -35,6 +95,7 @@ impl fmt::Display for Head {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum RefHead {
     Refheadword(String),
+    #[serde(with = "ref_brackets")]
     BlackBranckets(String, Option<(Inner, char)>),
     DAngleBrackets(String),
     RoundBrackets(String),
@@ -77,15 +138,19 @@ impl fmt::Display for Inner {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum H {
     Headword(String),
+    #[serde(with = "hw")]
     HW(String, Option<(Vec<Inner>, char)>),
     SquareBrackets(String),
     RoundBrackets(String),
     SquareBox(String),
+    #[serde(with = "subheadword")]
     Subheadword(ID, String),
+    #[serde(with = "brackets")]
     BlackBranckets(String, Option<(Inner, char)>),
     DAngleBrackets(String),
     AngleBrackets(String),
     Dia(String),
+    Unknown(Unknown),
 }
 
 impl fmt::Display for H {
@@ -115,12 +180,19 @@ impl fmt::Display for H {
                 write!(f, "{start}")
             }
             H::Subheadword(n, _s) => write!(f, "{}", n.0),
+            H::Unknown(u) => write!(f, "{}", u.text),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+named_variant!(hw(text: String, inner: Option<(Vec<Inner>, char)>));
+named_variant!(subheadword(id: ID, text: String));
+named_variant!(brackets(text: String, inner: Option<(Inner, char)>));
+named_variant!(ref_brackets(text: String, inner: Option<(Inner, char)>));
+
+#[derive(Debug)]
 pub struct Joshiword(pub ID, pub String);
+named_fields!(Joshiword { 0: id: ID, 1: text: String });
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ID(pub String);
@@ -140,19 +212,38 @@ pub struct Body(pub Vec<BodyContent>);
 #[derive(Debug, Serialize, Deserialize)]
 pub enum BodyContent {
     Accent(Vec<Accent>),
+    #[serde(with = "accent_round")]
     AccentRound(RoundBrackets, Option<Audio>),
+    #[serde(with = "reference")]
     Ref(Id, Vec<RefContent>),
     ConTable(Vec<ConTableContent>),
     SquareBox(String),
     Example(Example),
+    Unknown(Unknown),
+}
+
+/// An element the grammar has no rule for, only produced by `try_parse_xml_lenient`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Unknown {
+    pub tag: String,
+    pub class: Option<String>,
+    /// Attributes other than `class`.
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Unknown>,
+    /// All text inside the element, the text of `children` included.
+    pub text: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ConTableContent {
     Accent(Vec<Accent>),
+    #[serde(with = "accent_round")]
     AccentRound(RoundBrackets, Option<Audio>),
 }
 
+named_variant!(accent_round(accent: RoundBrackets, audio: Option<Audio>));
+named_variant!(reference(target: Id, text: Vec<RefContent>));
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum RefContent {
     Text(String),
@@ -167,8 +258,9 @@ pub struct Audio(pub String);
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SquareBox(pub String);
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct Accent(pub Option<AccentHead>, pub Vec<AccentText>);
+named_fields!(Accent { 0: head: Option<AccentHead>, 1: text: Vec<AccentText> });
 
 impl fmt::Display for Accent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -186,6 +278,7 @@ pub enum AccentText {
     Sound(String),
     SquareBox(String),
     NoteRef(String),
+    Unknown(Unknown),
 }
 
 impl fmt::Display for AccentText {
@@ -206,35 +299,71 @@ impl fmt::Display for AccentText {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RoundBrackets(pub Vec<AccentText>);
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct ExampleHead(pub ID, pub String);
+named_fields!(ExampleHead { 0: id: ID, 1: text: String });
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct Example(pub ExampleHead, pub Vec<ExampleContent>);
+named_fields!(Example { 0: head: ExampleHead, 1: content: Vec<ExampleContent> });
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ExampleContent {
     AccentExample(Vec<AccentText>),
     SquareBrackets(String),
+    #[serde(with = "reference")]
     Ref(Id, Vec<RefContent>),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct Josushi(
     pub JosushiNumber,
     pub Vec<Accent>,
     pub Vec<Indent>,
     pub Option<Notes>,
 );
-#[derive(Debug, Serialize, Deserialize)]
+named_fields!(Josushi {
+    0: number: JosushiNumber,
+    1: accents: Vec<Accent>,
+    2: indents: Vec<Indent>,
+    3: notes: Option<Notes>,
+});
+#[derive(Debug)]
 pub struct JosushiNumber(pub ID, pub String);
+named_fields!(JosushiNumber { 0: id: ID, 1: text: String });
 
 #[derive(Debug, Serialize, Deserialize)]
 
 pub struct Indent(pub Vec<AccentText>);
 #[derive(Debug, Serialize, Deserialize)]
 
-pub struct Notes(pub Vec<(Option<Num>, Vec<NoteContent>)>);
+pub struct Notes(#[serde(with = "notes")] pub Vec<(Option<Num>, Vec<NoteContent>)>);
+
+mod notes {
+    use super::*;
+
+    type Note = (Option<Num>, Vec<NoteContent>);
+
+    #[derive(Serialize, Deserialize)]
+    struct Named<N, C> {
+        num: N,
+        content: C,
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        notes: &[Note],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(notes.iter().map(|(num, content)| Named { num, content }))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Note>, D::Error> {
+        let notes = Vec::<Named<Option<Num>, Vec<NoteContent>>>::deserialize(deserializer)?;
+        Ok(notes.into_iter().map(|n| (n.num, n.content)).collect())
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Num(pub String);
@@ -245,6 +374,7 @@ pub enum NoteContent {
     SymbolBackslash(String),
     RoundBox(String),
     SymbolMacron(String),
+    #[serde(with = "reference")]
     Ref(Id, Vec<RefContent>),
 }
 
@@ -260,8 +390,9 @@ impl fmt::Display for Rb {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Rt(pub String);
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug)]
 pub struct Ruby(pub Rb, pub Rt);
+named_fields!(Ruby { 0: rb: Rb, 1: rt: Rt });
 
 impl fmt::Display for Ruby {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -0,0 +1,79 @@
+use nom::{
+    Parser,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    error::ErrorKind,
+    multi::many0,
+    sequence::{delimited, preceded},
+};
+
+use super::*;
+
+/// Last alternative where unknown markup is to be kept: any span or div, but only
+/// in lenient mode. In strict mode it fails without input consumed, so the error
+/// of the known alternatives is reported.
+pub(super) fn parse_unknown<'s>(mode: Mode) -> impl FnMut(&'s str) -> IResult<&'s str, Unknown> {
+    move |input| {
+        if mode == Mode::Strict {
+            return fail(input, Expected::Kind(ErrorKind::Alt));
+        }
+        let (rem, unknown) = parse_element(input)?;
+        if unknown.tag != "span" && unknown.tag != "div" {
+            return fail(input, Expected::Tag("span"));
+        }
+        Ok((rem, unknown))
+    }
+}
+
+enum Node {
+    Text(String),
+    Element(Unknown),
+}
+
+fn parse_element(input: &str) -> IResult<&str, Unknown> {
+    let (rem, (name, attrs)) = preceded(char('<'), (parse_identifier, parse_attrs)).parse(input)?;
+    if name.is_empty() {
+        return fail(input, Expected::Kind(ErrorKind::Tag));
+    }
+    let mut unknown = Unknown {
+        tag: name.into(),
+        class: None,
+        attrs: Vec::new(),
+        children: Vec::new(),
+        text: String::new(),
+    };
+    for (key, val) in attrs {
+        if key == "class" {
+            unknown.class = Some(val.into());
+        } else {
+            unknown.attrs.push((key.into(), val.into()));
+        }
+    }
+    if let Ok((rem, _)) = tag::<_, _, GrammarError<&str>>("/>").parse(rem) {
+        return Ok((rem, unknown));
+    }
+
+    let (rem, nodes) = preceded(
+        char('>'),
+        many0(alt((
+            text.map(|t| Node::Text(t.into())),
+            parse_element.map(Node::Element),
+        ))),
+    )
+    .parse(rem)?;
+    let (rem, close) = delimited(tag("</"), parse_identifier, char('>')).parse(rem)?;
+    if close != name {
+        return fail(rem, Expected::Kind(ErrorKind::Tag));
+    }
+    for node in nodes {
+        match node {
+            Node::Text(t) => unknown.text.push_str(&t),
+            Node::Element(child) => {
+                unknown.text.push_str(&child.text);
+                unknown.children.push(child);
+            }
+        }
+    }
+    Ok((rem, unknown))
+}
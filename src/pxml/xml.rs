@@ -1,5 +1,7 @@
+use std::fmt;
+
 use nom::{
-    IResult, Parser,
+    Parser,
     bytes::complete::{tag, take_while, take_while1},
     character::complete::{char, space0},
     error::{ErrorKind, ParseError},
@@ -7,22 +9,87 @@ use nom::{
     sequence::{delimited, preceded, terminated},
 };
 
-/*pub fn html_tag<'s,T:ToStr,O,F,E>(mut inner:F)-> impl FnMut(&'s str) -> IResult<&'s  str,(Vec<(&'s str,&'s str)>,O)> where F:Parser<&'s str,O,E>, E: ParseError<&'s str> {
-    move |input:&str| {
-        tuple((parse_open_tag::<T>,inner))(input)
+pub type IResult<I, O> = nom::IResult<I, O, GrammarError<I>>;
+
+pub type Attrs<'s> = Vec<(&'s str, &'s str)>;
+
+/// What the grammar was looking for where parsing failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    Prolog,
+    Tag(&'static str),
+    CloseTag(&'static str),
+    Class(&'static str, &'static str),
+    Attr(&'static str, &'static str),
+    HasAttr(&'static str),
+    Char(char),
+    End,
+    Kind(ErrorKind),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Prolog => write!(f, "xml declaration"),
+            Expected::Tag(tag) => write!(f, "<{tag}>"),
+            Expected::CloseTag(tag) => write!(f, "</{tag}>"),
+            Expected::Class(tag, class) => write!(f, "<{tag} class=\"{class}\">"),
+            Expected::Attr(key, val) => write!(f, "{key}=\"{val}\""),
+            Expected::HasAttr(key) => write!(f, "{key} attribute"),
+            Expected::Char(c) => write!(f, "'{c}'"),
+            Expected::End => write!(f, "end of page"),
+            Expected::Kind(ErrorKind::TakeWhile1) => write!(f, "text"),
+            Expected::Kind(kind) => write!(f, "{}", kind.description()),
+        }
+    }
+}
+
+/// nom error of the page grammar. Of several failed alternatives the one that got
+/// furthest into the input is kept, it is usually the one closest to the real problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarError<I> {
+    pub input: I,
+    pub expected: Expected,
+}
+
+impl<'s> GrammarError<&'s str> {
+    pub fn new(input: &'s str, expected: Expected) -> Self {
+        GrammarError { input, expected }
+    }
+}
+
+impl<'s> ParseError<&'s str> for GrammarError<&'s str> {
+    fn from_error_kind(input: &'s str, kind: ErrorKind) -> Self {
+        Self::new(input, Expected::Kind(kind))
+    }
+
+    fn append(_input: &'s str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn from_char(input: &'s str, c: char) -> Self {
+        Self::new(input, Expected::Char(c))
+    }
+
+    fn or(self, other: Self) -> Self {
+        if other.input.len() < self.input.len() {
+            other
+        } else {
+            self
+        }
     }
 }
-*/
 
-pub fn xml_tag<'s, O, F, E>(
-    tag: &'s str,
+pub(crate) fn fail<O>(input: &str, expected: Expected) -> IResult<&str, O> {
+    Err(nom::Err::Error(GrammarError::new(input, expected)))
+}
+
+pub fn xml_tag<'s, O, F>(
+    tag: &'static str,
     mut inner: F,
-) -> impl FnMut(&'s str) -> IResult<&'s str, (Vec<(&'s str, &'s str)>, O)>
+) -> impl FnMut(&'s str) -> IResult<&'s str, (Attrs<'s>, O)>
 where
-    //F: Parser<&'s str, O, E>,
-    F: Parser<&'s str, Error = E, Output = O>,
-    E: ParseError<&'s str>,
-    nom::Err<nom::error::Error<&'s str>>: From<nom::Err<E>>,
+    F: Parser<&'s str, Error = GrammarError<&'s str>, Output = O>,
 {
     move |input: &str| {
         let (input, attrs) = parse_open_tag(tag, input)?;
@@ -32,51 +99,38 @@ where
     }
 }
 
-pub trait VerifyClass<'s, O> {
-    fn verify_class(self, class: &'s str) -> IResult<&'s str, O>;
-}
-
-impl<'s, T, O> VerifyClass<'s, O> for T
+/// Like `xml_tag`, but the element has to have `class`. That is checked before the
+/// content is parsed, so an element of another class fails right at its start.
+pub fn xml_class_tag<'s, O, F>(
+    tag: &'static str,
+    class: &'static str,
+    mut inner: F,
+) -> impl FnMut(&'s str) -> IResult<&'s str, (Attrs<'s>, O)>
 where
-    T: Verify<'s, O>,
+    F: Parser<&'s str, Error = GrammarError<&'s str>, Output = O>,
 {
-    fn verify_class(self, class: &'s str) -> IResult<&'s str, O> {
-        self.verify("class", class)
-    }
-}
-
-pub trait Verify<'s, O> {
-    fn verify(self, key: &'s str, val: &'s str) -> IResult<&'s str, O>;
-}
-
-impl<'s, O> Verify<'s, O> for IResult<&'s str, (Vec<(&'s str, &'s str)>, O)> {
-    fn verify(self, key: &'s str, val: &'s str) -> IResult<&'s str, O> {
-        self.and_then(|(rem, (attrs, x))| {
-            if attrs.attr(key) != Some(val) {
-                Err(nom::Err::Error(ParseError::from_error_kind(
-                    rem,
-                    ErrorKind::Satisfy,
-                )))
-            } else {
-                Ok((rem, x))
-            }
-        })
+    move |input: &str| {
+        let (rem, attrs) = parse_open_tag(tag, input)?;
+        if attrs.attr("class") != Some(class) {
+            return fail(input, Expected::Class(tag, class));
+        }
+        let (rem, content) = inner.parse(rem)?;
+        let (rem, _) = parse_close_tag(tag, rem)?;
+        Ok((rem, (attrs, content)))
     }
 }
 
-impl<'s> Verify<'s, ()> for IResult<&'s str, Vec<(&'s str, &'s str)>> {
-    fn verify(self, key: &'s str, val: &'s str) -> IResult<&'s str, ()> {
-        self.and_then(|(rem, attrs)| {
-            if attrs.attr(key) != Some(val) {
-                Err(nom::Err::Error(ParseError::from_error_kind(
-                    rem,
-                    ErrorKind::Satisfy,
-                )))
-            } else {
-                Ok((rem, ()))
-            }
-        })
-    }
+/// `xml_class_tag` without the attributes.
+pub fn xml_class<'s, O, F>(
+    tag: &'static str,
+    class: &'static str,
+    inner: F,
+) -> impl FnMut(&'s str) -> IResult<&'s str, O>
+where
+    F: Parser<&'s str, Error = GrammarError<&'s str>, Output = O>,
+{
+    let mut parser = xml_class_tag(tag, class, inner);
+    move |input: &str| parser(input).map(|(rem, (_, content))| (rem, content))
 }
 
 pub fn kana(input: &str) -> IResult<&str, &str> {
@@ -85,6 +139,7 @@ pub fn kana(input: &str) -> IResult<&str, &str> {
     take_while1(|c: char| ('あ'..='゜').contains(&c) || ('ァ'..='ー').contains(&c))(input)
 }
 
+#[cfg(test)]
 pub fn kanji(input: &str) -> IResult<&str, &str> {
     //take_while(|c: char| c != '<')(input)
     //take_until("<")(input)
@@ -123,56 +178,64 @@ pub fn ref_text(input: &str) -> IResult<&str, &str> {
     })(input)
 }
 
-pub fn empty_xml_tag<'s>(t: &'s str, input: &'s str) -> IResult<&'s str, Vec<(&'s str, &'s str)>> {
-    delimited(char('<'), (parse_identifier, parse_attrs), tag("/>"))
-        .parse(input)
-        .and_then(|(rem, (tag, attr))| {
-            if tag != t {
-                Err(nom::Err::Error(ParseError::from_error_kind(
-                    input,
-                    ErrorKind::Satisfy,
-                )))
-            } else {
-                Ok((rem, attr))
-            }
-        })
-}
-
-fn parse_open_tag<'s>(tag: &'s str, input: &'s str) -> IResult<&'s str, Vec<(&'s str, &'s str)>> {
-    delimited(char('<'), (parse_identifier, parse_attrs), char('>'))
-        .parse(input)
-        .and_then(|(rem, (t, attr))| {
-            if tag != t {
-                Err(nom::Err::Error(ParseError::from_error_kind(
-                    input,
-                    ErrorKind::Satisfy,
-                )))
-            } else {
-                Ok((rem, attr))
-            }
-        })
-}
-
-fn parse_close_tag<'s>(t: &'s str, input: &'s str) -> IResult<&'s str, &'s str> {
-    delimited(tag("</"), parse_identifier, char('>'))
-        .parse(input)
-        .and_then(|(rem, parsed)| {
-            if parsed != t {
-                Err(nom::Err::Error(ParseError::from_error_kind(
-                    input,
-                    ErrorKind::Satisfy,
-                )))
-            } else {
-                Ok((rem, parsed))
-            }
-        })
+pub fn empty_xml_tag<'s>(
+    t: &'static str,
+    input: &'s str,
+) -> IResult<&'s str, Vec<(&'s str, &'s str)>> {
+    let Ok((rem, (parsed, attr))) =
+        delimited(char('<'), (parse_identifier, parse_attrs), tag("/>")).parse(input)
+    else {
+        return fail(input, Expected::Tag(t));
+    };
+    if parsed != t {
+        return fail(input, Expected::Tag(t));
+    }
+    Ok((rem, attr))
+}
+
+/// An empty element with `class`, like `<div class="body"/>`.
+pub fn empty_xml_class<'s>(
+    t: &'static str,
+    class: &'static str,
+    input: &'s str,
+) -> IResult<&'s str, ()> {
+    let (rem, attrs) = empty_xml_tag(t, input)?;
+    if attrs.attr("class") != Some(class) {
+        return fail(input, Expected::Class(t, class));
+    }
+    Ok((rem, ()))
+}
+
+fn parse_open_tag<'s>(
+    tag: &'static str,
+    input: &'s str,
+) -> IResult<&'s str, Vec<(&'s str, &'s str)>> {
+    let Ok((rem, (t, attr))) =
+        delimited(char('<'), (parse_identifier, parse_attrs), char('>')).parse(input)
+    else {
+        return fail(input, Expected::Tag(tag));
+    };
+    if tag != t {
+        return fail(input, Expected::Tag(tag));
+    }
+    Ok((rem, attr))
+}
+
+fn parse_close_tag<'s>(t: &'static str, input: &'s str) -> IResult<&'s str, &'s str> {
+    let Ok((rem, parsed)) = delimited(tag("</"), parse_identifier, char('>')).parse(input) else {
+        return fail(input, Expected::CloseTag(t));
+    };
+    if parsed != t {
+        return fail(input, Expected::CloseTag(t));
+    }
+    Ok((rem, parsed))
 }
 
 fn parse_attr(input: &str) -> IResult<&str, (&str, &str)> {
     (terminated(parse_identifier, char('=')), parse_str).parse(input)
 }
 
-fn parse_attrs(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
+pub(crate) fn parse_attrs(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
     many0(preceded(space0, parse_attr)).parse(input)
 }
 
@@ -180,7 +243,7 @@ fn parse_str(input: &str) -> IResult<&str, &str> {
     delimited(char('"'), take_while(|c: char| c != '"'), char('"')).parse(input)
 }
 
-fn parse_identifier(input: &str) -> IResult<&str, &str> {
+pub(crate) fn parse_identifier(input: &str) -> IResult<&str, &str> {
     take_while(|c: char| c.is_alphabetic() || c == '_' || c == '-')(input)
 }
 
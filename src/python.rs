@@ -0,0 +1,164 @@
+//! The `accent_dict` Python module the Anki add-on loads.
+
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyFloat, PyList, PyString},
+};
+
+use crate::{
+    _look_up, _look_up_entries, Error, MonokakidoDict, TextRenderer, Unpacked, WordType, coverage,
+    gen_svg, hum, verify, waveform,
+};
+
+/// Raises `OSError` if the dictionary can't be read or one of the pages of
+/// `vocab` doesn't parse.
+#[pyfunction(signature = (path, vocab, word_type=None))]
+fn look_up(
+    py: Python<'_>,
+    path: String,
+    vocab: String,
+    word_type: Option<WordType>,
+) -> PyResult<Vec<Unpacked>> {
+    py.detach(|| _look_up(&path, &vocab, word_type))
+        .map_err(|e| PyIOError::new_err(format!("{e:?}")))
+}
+
+/// Like `look_up`, but returns the complete parsed entries, with examples,
+/// references, notes and tables, as nested dicts and lists. Fields are dict
+/// keys like `id`, `head_groups` and `josushi`.
+#[pyfunction(signature = (path, vocab, word_type=None))]
+fn look_up_entries<'py>(
+    py: Python<'py>,
+    path: String,
+    vocab: String,
+    word_type: Option<WordType>,
+) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    let entries = py
+        .detach(|| _look_up_entries(&path, &vocab, word_type))
+        .map_err(|e| PyIOError::new_err(format!("{e:?}")))?;
+    let entries = entries
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    entries.iter().map(|entry| json_to_py(py, entry)).collect()
+}
+
+/// Like `look_up`, but returns the entries as text for a terminal, with a pitch
+/// graph below every accent.
+#[pyfunction(signature = (path, vocab, word_type=None, ascii=false))]
+fn look_up_text(
+    py: Python<'_>,
+    path: String,
+    vocab: String,
+    word_type: Option<WordType>,
+    ascii: bool,
+) -> PyResult<String> {
+    py.detach(|| {
+        let mut renderer = TextRenderer::new();
+        renderer.set_ascii(ascii);
+        let entries = _look_up_entries(&path, &vocab, word_type)?;
+        Ok(entries
+            .iter()
+            .map(|item| renderer.render(item))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    })
+    .map_err(|e: Error| PyIOError::new_err(format!("{e:?}")))
+}
+
+#[pyfunction]
+fn gen_pitch_svg(pitch_pattern: String) -> String {
+    gen_svg::gen_svg(&pitch_pattern)
+}
+
+#[pyfunction]
+fn gen_pitch_audio(py: Python<'_>, pitch_pattern: String) -> Vec<u8> {
+    py.detach(|| hum::gen_hum_wav(&pitch_pattern))
+}
+
+#[pyfunction]
+fn get_sound(py: Python<'_>, path: String, file_name: String) -> Vec<u8> {
+    let file_name = file_name.strip_suffix(".aac").unwrap_or(&file_name);
+    py.detach(|| {
+        let dict = MonokakidoDict::open_with_path(&path).unwrap();
+        dict.audio.get(file_name).unwrap().into_owned()
+    })
+}
+
+#[pyfunction]
+fn gen_waveform_svg(py: Python<'_>, path: String, file_name: String) -> String {
+    let file_name = file_name.strip_suffix(".aac").unwrap_or(&file_name);
+    py.detach(|| {
+        let dict = MonokakidoDict::open_with_path(&path).unwrap();
+        waveform::gen_waveform_svg(&dict.audio.get(file_name).unwrap()).unwrap()
+    })
+}
+
+/// Checks the dictionary files, returns one line per problem found.
+#[pyfunction]
+#[pyo3(name = "verify")]
+fn verify_dict(py: Python<'_>, path: String) -> Vec<String> {
+    py.detach(|| {
+        verify(&path)
+            .problems
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    })
+}
+
+/// Parses every page strictly and leniently, returns the coverage report as text.
+#[pyfunction]
+#[pyo3(name = "coverage")]
+fn parse_coverage(py: Python<'_>, path: String) -> PyResult<String> {
+    py.detach(|| coverage(&path))
+        .map(|coverage| coverage.to_string())
+        .map_err(|e| PyIOError::new_err(format!("{e:?}")))
+}
+
+/// A Python module implemented in Rust.
+#[pymodule]
+fn accent_dict(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(look_up, m)?)?;
+    m.add_function(wrap_pyfunction!(look_up_entries, m)?)?;
+    m.add_function(wrap_pyfunction!(look_up_text, m)?)?;
+    m.add_function(wrap_pyfunction!(get_sound, m)?)?;
+    m.add_function(wrap_pyfunction!(gen_pitch_svg, m)?)?;
+    m.add_function(wrap_pyfunction!(gen_waveform_svg, m)?)?;
+    m.add_function(wrap_pyfunction!(gen_pitch_audio, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_coverage, m)?)?;
+    m.add_class::<WordType>()?;
+    Ok(())
+}
+
+/// Converts a `serde_json::Value` to the matching Python builtin types.
+fn json_to_py<'py>(py: Python<'py>, value: &serde_json::Value) -> PyResult<Bound<'py, PyAny>> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+            _ => PyFloat::new(py, n.as_f64().unwrap_or(f64::NAN)).into_any(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any(),
+        Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(json_to_py(py, value)?)?;
+            }
+            list.into_any()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
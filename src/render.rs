@@ -0,0 +1,24 @@
+//! Renderers that turn a parsed page into something to show to a reader.
+
+mod html;
+mod text;
+
+pub use html::HtmlRenderer;
+pub use text::TextRenderer;
+
+use crate::AccentText;
+
+/// The accent marks and kana of `texts`, as `Accent` displays them.
+fn accent_string(texts: &[AccentText]) -> String {
+    texts.iter().map(|t| t.to_string()).collect()
+}
+
+/// True if every word of `accent` has a drop or flat mark, which `gen_svg` needs
+/// to draw it.
+fn has_pitch(accent: &str) -> bool {
+    let accent = accent.trim();
+    !accent.is_empty()
+        && accent
+            .split('・')
+            .all(|word| word.contains(['＼', '▔']) && word.chars().count() > 1)
+}
@@ -0,0 +1,464 @@
+//! A page as an HTML fragment that needs no files of the dictionary app: pitch
+//! diagrams are inline SVG, furigana are `<ruby>` and sounds are `<audio>`
+//! elements pointing at exported clips.
+
+use std::fmt::Write;
+
+use super::{accent_string, has_pitch};
+use crate::{
+    Accent, AccentText, Body, BodyContent, ConTableContent, DicItem, Example, ExampleContent, H,
+    Head, Id, Inner, Josushi, NoteContent, RefContent, RefHead, Ruby, circle::to_circle,
+    gen_svg::gen_svg,
+};
+
+/// Styles for the classes used in the fragment, replacing `nhk_accent.css`.
+const STYLE: &str = "<style>\
+.dic-item .head{font-size:1.3em}\
+.dic-item .hw,.dic-item .black_branckets{margin-left:.3em}\
+.dic-item .body,.dic-item .josushi{margin:.3em 0 .6em 1em}\
+.dic-item .accent{display:inline-block;margin-right:1em}\
+.dic-item .accent svg{height:2.2em;width:auto;vertical-align:middle}\
+.dic-item .accent audio{height:1.6em;width:8em;vertical-align:middle}\
+.dic-item .square_box{border:1px solid;padding:0 .1em;font-size:.8em}\
+.dic-item .example{display:block;margin-left:1em}\
+.dic-item .notes{font-size:.9em}\
+</style>";
+
+/// Renders parsed pages as standalone HTML fragments.
+#[derive(Debug, Clone)]
+pub struct HtmlRenderer {
+    audio_dir: String,
+    ref_prefix: String,
+    style: bool,
+}
+
+impl Default for HtmlRenderer {
+    fn default() -> Self {
+        HtmlRenderer {
+            audio_dir: String::new(),
+            ref_prefix: "#".to_string(),
+            style: true,
+        }
+    }
+}
+
+impl HtmlRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory or URL the audio clips were exported to, as `<id>.aac`. By
+    /// default clips are referenced by file name only, like Anki media.
+    pub fn set_audio_dir(&mut self, dir: &str) {
+        self.audio_dir = dir.to_string();
+    }
+
+    /// Prepended to the page id in links to other entries. `#` by default, which
+    /// links to the entry rendered into the same document.
+    pub fn set_ref_prefix(&mut self, prefix: &str) {
+        self.ref_prefix = prefix.to_string();
+    }
+
+    /// Whether a `<style>` element is put before the entry, on by default.
+    pub fn set_style(&mut self, style: bool) {
+        self.style = style;
+    }
+
+    pub fn render(&self, item: &DicItem) -> String {
+        let DicItem(Id(id), head_gs, josushi) = item;
+        let mut html = String::new();
+        if self.style {
+            html.push_str(STYLE);
+        }
+        let _ = write!(html, "<div class=\"dic-item\" id=\"{}\">", escape(id));
+        for head_g in head_gs {
+            html.push_str("<div class=\"head-g\">");
+            self.head(&mut html, &head_g.0);
+            self.body(&mut html, &head_g.1);
+            html.push_str("</div>");
+        }
+        for josushi in josushi {
+            self.josushi(&mut html, josushi);
+        }
+        html.push_str("</div>");
+        html
+    }
+
+    fn head(&self, html: &mut String, head: &Head) {
+        html.push_str("<div class=\"head\">");
+        match head {
+            Head::H(hs) => {
+                for h in hs {
+                    self.h(html, h);
+                }
+            }
+            Head::Joshiword(joshiword) => anchored(html, "joshiword", &joshiword.0.0, &joshiword.1),
+            Head::Ref(refs) => {
+                html.push_str("<span class=\"ref\">");
+                for r in refs {
+                    match r {
+                        RefHead::Refheadword(s) => span(html, "refheadword", s),
+                        RefHead::BlackBranckets(s, inner) => {
+                            open(html, "black_branckets");
+                            html.push_str(&escape(s));
+                            if let Some((inner, end)) = inner {
+                                self.inner(html, inner);
+                                html.push(*end);
+                            }
+                            html.push_str("</span>");
+                        }
+                        RefHead::DAngleBrackets(s) => span(html, "d_angle_brackets", s),
+                        RefHead::RoundBrackets(s) => span(html, "round_brackets", s),
+                        RefHead::SquareBrackets(s) => span(html, "square_brackets", s),
+                    }
+                }
+                html.push_str("</span>");
+            }
+            Head::None => {}
+        }
+        html.push_str("</div>");
+    }
+
+    fn h(&self, html: &mut String, h: &H) {
+        match h {
+            H::Headword(s) => span(html, "headword", s),
+            H::HW(s, inner) => {
+                open(html, "hw");
+                html.push_str(&escape(s));
+                if let Some((inners, end)) = inner {
+                    for inner in inners {
+                        self.inner(html, inner);
+                    }
+                    html.push(*end);
+                }
+                html.push_str("</span>");
+            }
+            H::SquareBrackets(s) => span(html, "square_brackets", s),
+            H::RoundBrackets(s) => span(html, "round_brackets", s),
+            H::SquareBox(s) => span(html, "square_box", s),
+            H::Subheadword(id, s) => anchored(html, "subheadword", &id.0, s),
+            H::BlackBranckets(s, inner) => {
+                open(html, "black_branckets");
+                html.push_str(&escape(s));
+                if let Some((inner, end)) = inner {
+                    self.inner(html, inner);
+                    html.push(*end);
+                }
+                html.push_str("</span>");
+            }
+            H::DAngleBrackets(s) => span(html, "d_angle_brackets", s),
+            H::AngleBrackets(s) => span(html, "angle_brackets", s),
+            H::Dia(s) => span(html, "dia", s),
+            H::Unknown(unknown) => span(html, "unknown", &unknown.text),
+        }
+    }
+
+    fn inner(&self, html: &mut String, inner: &Inner) {
+        match inner {
+            Inner::DAngleBrackets(s) => span(html, "d_angle_brackets", &s.0),
+            Inner::Ruby(rubies) => {
+                for (ruby, kana) in rubies {
+                    furigana(html, ruby);
+                    if let Some(kana) = kana {
+                        html.push_str(&escape(kana));
+                    }
+                }
+            }
+            Inner::RoundBrackets(s) => span(html, "round_brackets", s),
+            Inner::Text(s) | Inner::Span(s) => html.push_str(&escape(s)),
+        }
+    }
+
+    fn body(&self, html: &mut String, body: &Body) {
+        html.push_str("<div class=\"body\">");
+        for content in &body.0 {
+            match content {
+                BodyContent::Accent(accents) => {
+                    for accent in accents {
+                        self.accent(html, accent);
+                    }
+                }
+                BodyContent::AccentRound(round, audio) => {
+                    self.accent_round(html, &round.0, audio.as_ref().map(|a| a.0.as_str()))
+                }
+                BodyContent::Ref(id, content) => self.reference(html, id, content),
+                BodyContent::ConTable(table) => {
+                    html.push_str("<div class=\"con_table\">");
+                    for content in table {
+                        match content {
+                            ConTableContent::Accent(accents) => {
+                                for accent in accents {
+                                    self.accent(html, accent);
+                                }
+                            }
+                            ConTableContent::AccentRound(round, audio) => self.accent_round(
+                                html,
+                                &round.0,
+                                audio.as_ref().map(|a| a.0.as_str()),
+                            ),
+                        }
+                    }
+                    html.push_str("</div>");
+                }
+                BodyContent::SquareBox(s) => span(html, "square_box", s),
+                BodyContent::Example(example) => self.example(html, example),
+                BodyContent::Unknown(unknown) => span(html, "unknown", &unknown.text),
+            }
+        }
+        html.push_str("</div>");
+    }
+
+    fn accent(&self, html: &mut String, accent: &Accent) {
+        open(html, "accent");
+        if let Some(head) = &accent.0 {
+            span(html, "square_box", &head.0.0);
+        }
+        self.accent_texts(html, &accent.1);
+        html.push_str("</span>");
+    }
+
+    /// An accent in brackets, with a sound either inside or after the brackets.
+    fn accent_round(&self, html: &mut String, texts: &[AccentText], sound: Option<&str>) {
+        open(html, "accent accent_round");
+        self.accent_texts(html, texts);
+        if let Some(sound) = sound {
+            self.audio(html, sound);
+        }
+        html.push_str("</span>");
+    }
+
+    /// The accent string, its pitch diagram, note references and sounds.
+    fn accent_texts(&self, html: &mut String, texts: &[AccentText]) {
+        let accent = accent_string(texts);
+        span(html, "accent_text", &accent);
+        if has_pitch(&accent) {
+            html.push_str(gen_svg(&accent).trim());
+        }
+        for text in texts {
+            match text {
+                AccentText::NoteRef(s) => {
+                    let _ = write!(html, "<sup class=\"note_ref\">{}</sup>", escape(s));
+                }
+                AccentText::Sound(href) => self.audio(html, href),
+                _ => {}
+            }
+        }
+    }
+
+    fn audio(&self, html: &mut String, href: &str) {
+        let src = if self.audio_dir.is_empty() {
+            href.to_string()
+        } else {
+            format!("{}/{href}", self.audio_dir.trim_end_matches('/'))
+        };
+        let _ = write!(
+            html,
+            "<audio controls preload=\"none\" src=\"{}\"></audio>",
+            escape(&src)
+        );
+    }
+
+    /// A link to another page. Links into the app's appendix can't be followed
+    /// outside of it and are rendered as text.
+    fn reference(&self, html: &mut String, id: &Id, content: &[RefContent]) {
+        let text: String = content
+            .iter()
+            .map(|c| match c {
+                RefContent::Text(s) | RefContent::RoundBrackets(s) | RefContent::TextSpan(s) => {
+                    s.as_str()
+                }
+            })
+            .collect();
+        if id.0.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            let _ = write!(
+                html,
+                "<a class=\"ref\" href=\"{}{}\">{}</a>",
+                escape(&self.ref_prefix),
+                escape(&id.0),
+                escape(&text)
+            );
+        } else {
+            span(html, "ref", &text);
+        }
+    }
+
+    fn example(&self, html: &mut String, example: &Example) {
+        let Example(head, contents) = example;
+        let _ = write!(
+            html,
+            "<span class=\"example\" id=\"{}\">",
+            escape(&head.0.0)
+        );
+        span(html, "ex_head", &head.1);
+        for content in contents {
+            match content {
+                ExampleContent::AccentExample(texts) => {
+                    open(html, "accent accent_example");
+                    self.accent_texts(html, texts);
+                    html.push_str("</span>");
+                }
+                ExampleContent::SquareBrackets(s) => span(html, "square_brackets", s),
+                ExampleContent::Ref(id, content) => self.reference(html, id, content),
+            }
+        }
+        html.push_str("</span>");
+    }
+
+    fn josushi(&self, html: &mut String, josushi: &Josushi) {
+        let Josushi(number, accents, indents, notes) = josushi;
+        let _ = write!(
+            html,
+            "<div class=\"josushi\" id=\"{}\">",
+            escape(&number.0.0)
+        );
+        span(html, "subheadword josushi", &number.1);
+        for accent in accents {
+            self.accent(html, accent);
+        }
+        for indent in indents {
+            html.push_str("<div class=\"indent\">");
+            open(html, "accent");
+            self.accent_texts(html, &indent.0);
+            html.push_str("</span></div>");
+        }
+        if let Some(notes) = notes {
+            html.push_str("<div class=\"notes\">");
+            for (num, contents) in &notes.0 {
+                html.push_str("<div class=\"note\">");
+                if let Some(num) = num {
+                    span(html, "note_num", &num.0);
+                }
+                for content in contents {
+                    match content {
+                        NoteContent::Text(s)
+                        | NoteContent::SymbolBackslash(s)
+                        | NoteContent::SymbolMacron(s) => html.push_str(&escape(s)),
+                        NoteContent::RoundBox(s) => html.push_str(&escape(&to_circle(s))),
+                        NoteContent::Accent(accent) => self.accent(html, accent),
+                        NoteContent::Ref(id, content) => self.reference(html, id, content),
+                    }
+                }
+                html.push_str("</div>");
+            }
+            html.push_str("</div>");
+        }
+        html.push_str("</div>");
+    }
+}
+
+fn open(html: &mut String, class: &str) {
+    let _ = write!(html, "<span class=\"{class}\">");
+}
+
+fn span(html: &mut String, class: &str, text: &str) {
+    let _ = write!(html, "<span class=\"{class}\">{}</span>", escape(text));
+}
+
+/// A span that links to `id` can point at.
+fn anchored(html: &mut String, class: &str, id: &str, text: &str) {
+    let _ = write!(
+        html,
+        "<span class=\"{class}\" id=\"{}\">{}</span>",
+        escape(id),
+        escape(text)
+    );
+}
+
+fn furigana(html: &mut String, ruby: &Ruby) {
+    let Ruby(rb, rt) = ruby;
+    let _ = write!(
+        html,
+        "<ruby>{}<rt>{}</rt></ruby>",
+        escape(&rb.0),
+        escape(&rt.0)
+    );
+}
+
+/// Escapes text for use in element content and quoted attribute values. Page
+/// text keeps the character references of the XML, those are passed through.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for (i, c) in s.char_indices() {
+        match c {
+            '&' if is_reference(&s[i..]) => escaped.push('&'),
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// True if `s` starts with a reference like `&amp;` or `&#12354;`.
+fn is_reference(s: &str) -> bool {
+    let Some((name, _)) = s[1..].split_once(';') else {
+        return false;
+    };
+    let name = name.strip_prefix('#').unwrap_or(name);
+    !name.is_empty() && name.len() <= 10 && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixture::{IPPON_SOUND, SAKURA_SOUND, page_xml},
+        try_parse_xml, try_parse_xml_lenient,
+    };
+
+    #[test]
+    fn entry() {
+        let item = try_parse_xml(&page_xml(1, "さくら")).unwrap();
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_audio_dir("media/");
+        let html = renderer.render(&item);
+        assert!(html.starts_with("<style>"));
+        assert!(html.contains("<div class=\"dic-item\" id=\"00001\">"));
+        assert!(html.contains("<span class=\"headword\">さくら</span>"));
+        assert!(html.contains("<span class=\"hw\">【桜】</span>"));
+        assert!(html.contains("<span class=\"accent_text\">サクラ▔</span><svg"));
+        assert!(html.contains(&format!("src=\"media/{SAKURA_SOUND}.aac\"")));
+        assert!(!html.contains("HMDicAudio.png"));
+
+        renderer.set_style(false);
+        let item = try_parse_xml(&page_xml(3, "―ほん")).unwrap();
+        let html = renderer.render(&item);
+        assert!(html.starts_with("<div class=\"dic-item\" id=\"00003\">"));
+        assert!(html.contains(
+            "<div class=\"josushi\" id=\"00003-0001\"><span class=\"subheadword josushi\">1</span>"
+        ));
+        assert!(html.contains(&format!("src=\"media/{IPPON_SOUND}.aac\"")));
+        assert_eq!(html.matches("<svg").count(), 2);
+    }
+
+    #[test]
+    fn furigana_and_links() {
+        let xml = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<html><head></head><body><span class=\"dic-item\" id=\"00009\"><span class=\"head-g\"><div class=\"head\"><span class=\"h\"><span class=\"headword\">あ&amp;い</span><span class=\"hw\">【<ruby><rb>愛</rb><rt>あい</rt></ruby>】</span></span></div><div class=\"body\"><span class=\"ref\"><a href=\"00001\"><text>☞</text>さくら</a></span><span class=\"ref\"><a href=\"appendix/furoku.html#p1\"><text>☞</text>付録</a></span></div></span></span></body></html>\n";
+        let item = try_parse_xml_lenient(xml).unwrap();
+        let mut renderer = HtmlRenderer::new();
+        renderer.set_style(false);
+        renderer.set_ref_prefix("entry://");
+        let html = renderer.render(&item);
+        assert!(html.contains("<span class=\"headword\">あ&amp;い</span>"));
+        assert!(html.contains("<span class=\"hw\">【<ruby>愛<rt>あい</rt></ruby>】</span>"));
+        assert!(html.contains("<a class=\"ref\" href=\"entry://00001\">☞さくら</a>"));
+        assert!(html.contains("<span class=\"ref\">☞付録</span>"));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+        assert_eq!(escape("&amp;&#12354;&x"), "&amp;&#12354;&amp;x");
+    }
+
+    #[test]
+    fn pitch_needed_for_diagram() {
+        assert!(has_pitch("ジュ＼ー・ゴ＼ニチ"));
+        assert!(has_pitch("サクラ▔"));
+        assert!(!has_pitch("ア・カ＼"));
+        assert!(!has_pitch("＼"));
+        assert!(!has_pitch(""));
+    }
+}
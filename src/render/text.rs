@@ -0,0 +1,387 @@
+//! A page as plain text for a terminal, with a pitch graph drawn above every
+//! accent.
+//!
+//! ```text
+//! さくら 【桜】
+//!   サクラ▔ [0] ♪20170630130152
+//!       ┌───
+//!     ──┘
+//!     サクラ
+//! ```
+
+use std::fmt::Write;
+
+use super::accent_string;
+use crate::{
+    Accent, AccentText, BodyContent, ConTableContent, DicItem, ExampleContent, H, Head, Inner,
+    Josushi, NoteContent, RefContent, RefHead,
+    circle::to_circle,
+    gen_svg::VOICED,
+    pitch::{PitchWord, parse_pitch},
+};
+
+const INDENT: &str = "  ";
+
+/// Characters the graph is drawn with.
+struct Lines {
+    line: char,
+    rise_top: char,
+    rise_bottom: char,
+    fall_top: char,
+    fall_bottom: char,
+}
+
+const BOX_DRAWING: Lines = Lines {
+    line: '─',
+    rise_top: '┌',
+    rise_bottom: '┘',
+    fall_top: '┐',
+    fall_bottom: '└',
+};
+
+const ASCII: Lines = Lines {
+    line: '-',
+    rise_top: '+',
+    rise_bottom: '+',
+    fall_top: '+',
+    fall_bottom: '+',
+};
+
+/// Renders parsed pages as indented plain text.
+#[derive(Debug, Clone)]
+pub struct TextRenderer {
+    ascii: bool,
+    graph: bool,
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self {
+        TextRenderer {
+            ascii: false,
+            graph: true,
+        }
+    }
+}
+
+impl TextRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draws the pitch graph with `-` and `+` instead of box drawing characters.
+    pub fn set_ascii(&mut self, ascii: bool) {
+        self.ascii = ascii;
+    }
+
+    /// Whether a pitch graph is drawn below every accent, on by default.
+    pub fn set_graph(&mut self, graph: bool) {
+        self.graph = graph;
+    }
+
+    pub fn render(&self, item: &DicItem) -> String {
+        let mut text = String::new();
+        for head_g in &item.1 {
+            let head = head_text(&head_g.0);
+            if !head.is_empty() {
+                text.push_str(&head);
+                text.push('\n');
+            }
+            for content in &head_g.1.0 {
+                self.body_content(&mut text, content);
+            }
+        }
+        for josushi in &item.2 {
+            self.josushi(&mut text, josushi);
+        }
+        text
+    }
+
+    fn body_content(&self, text: &mut String, content: &BodyContent) {
+        match content {
+            BodyContent::Accent(accents) => {
+                for accent in accents {
+                    self.accent(text, INDENT, accent);
+                }
+            }
+            BodyContent::AccentRound(round, audio) => {
+                let sound = audio.as_ref().map(|a| a.0.as_str());
+                self.accent_texts(text, INDENT, &round.0, sound);
+            }
+            BodyContent::Ref(_, content) => {
+                let _ = writeln!(text, "{INDENT}{}", ref_text(content));
+            }
+            BodyContent::ConTable(table) => {
+                for content in table {
+                    match content {
+                        ConTableContent::Accent(accents) => {
+                            for accent in accents {
+                                self.accent(text, INDENT, accent);
+                            }
+                        }
+                        ConTableContent::AccentRound(round, audio) => {
+                            let sound = audio.as_ref().map(|a| a.0.as_str());
+                            self.accent_texts(text, INDENT, &round.0, sound);
+                        }
+                    }
+                }
+            }
+            BodyContent::SquareBox(s) => {
+                let _ = writeln!(text, "{INDENT}[{s}]");
+            }
+            BodyContent::Example(example) => {
+                let _ = writeln!(text, "{INDENT}{}", example.0.1);
+                let indent = INDENT.repeat(2);
+                for content in &example.1 {
+                    match content {
+                        ExampleContent::AccentExample(texts) => {
+                            self.accent_texts(text, &indent, texts, None)
+                        }
+                        ExampleContent::SquareBrackets(s) => {
+                            let _ = writeln!(text, "{indent}{s}");
+                        }
+                        ExampleContent::Ref(_, content) => {
+                            let _ = writeln!(text, "{indent}{}", ref_text(content));
+                        }
+                    }
+                }
+            }
+            BodyContent::Unknown(unknown) => {
+                let _ = writeln!(text, "{INDENT}{}", unknown.text);
+            }
+        }
+    }
+
+    fn josushi(&self, text: &mut String, josushi: &Josushi) {
+        let Josushi(number, accents, indents, notes) = josushi;
+        let _ = writeln!(text, "{}", number.1);
+        for accent in accents {
+            self.accent(text, INDENT, accent);
+        }
+        let indent = INDENT.repeat(2);
+        for indented in indents {
+            self.accent_texts(text, &indent, &indented.0, None);
+        }
+        for (num, contents) in notes.iter().flat_map(|notes| &notes.0) {
+            let mut note = String::new();
+            if let Some(num) = num {
+                note.push_str(&num.0);
+            }
+            for content in contents {
+                match content {
+                    NoteContent::Text(s)
+                    | NoteContent::SymbolBackslash(s)
+                    | NoteContent::SymbolMacron(s) => note.push_str(s),
+                    NoteContent::RoundBox(s) => note.push_str(&to_circle(s)),
+                    NoteContent::Accent(accent) => note.push_str(&accent.to_string()),
+                    NoteContent::Ref(_, content) => note.push_str(&ref_text(content)),
+                }
+            }
+            let _ = writeln!(text, "{INDENT}{}", note.trim());
+        }
+    }
+
+    fn accent(&self, text: &mut String, indent: &str, accent: &Accent) {
+        let mut indent = indent.to_string();
+        if let Some(head) = &accent.0 {
+            let _ = writeln!(text, "{indent}[{}]", head.0.0);
+            indent.push_str(INDENT);
+        }
+        self.accent_texts(text, &indent, &accent.1, None);
+    }
+
+    /// A line with the accent, its downsteps and sounds, and the graph below it.
+    fn accent_texts(
+        &self,
+        text: &mut String,
+        indent: &str,
+        texts: &[AccentText],
+        sound: Option<&str>,
+    ) {
+        let accent = accent_string(texts);
+        let words = parse_pitch(&accent);
+        let mut line = format!("{indent}{}", accent.trim());
+        if !words.is_empty() {
+            let downsteps: Vec<String> = words
+                .iter()
+                .map(|w| w.downstep.unwrap_or(0).to_string())
+                .collect();
+            let _ = write!(line, " [{}]", downsteps.join("・"));
+        }
+        let sounds = texts
+            .iter()
+            .filter_map(|t| match t {
+                AccentText::Sound(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .chain(sound);
+        for sound in sounds {
+            let _ = write!(line, " ♪{}", sound.strip_suffix(".aac").unwrap_or(sound));
+        }
+        let _ = writeln!(text, "{line}");
+
+        if self.graph && !words.is_empty() {
+            let lines = if self.ascii { &ASCII } else { &BOX_DRAWING };
+            let indent = format!("{indent}{INDENT}");
+            for row in graph(&words, lines) {
+                let _ = writeln!(text, "{indent}{}", row.trim_end());
+            }
+        }
+    }
+}
+
+/// The high line, the low line and the kana of `words`. Every mora takes the
+/// columns its kana take in a terminal, a change of pitch is drawn in the first
+/// column of the mora it changes on. Odaka words get a drop after the last mora.
+fn graph(words: &[PitchWord], lines: &Lines) -> [String; 3] {
+    let [mut high, mut low, mut kana] = [String::new(), String::new(), String::new()];
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            high.push_str("  ");
+            low.push_str("  ");
+            kana.push('・');
+        }
+        let mut last_high = None;
+        for mora in &word.morae {
+            let width = width(&mora.text);
+            let (top, bottom) = match (last_high, mora.high) {
+                (Some(false), true) => (lines.rise_top, lines.rise_bottom),
+                (Some(true), false) => (lines.fall_top, lines.fall_bottom),
+                (_, true) => (lines.line, ' '),
+                (_, false) => (' ', lines.line),
+            };
+            high.push(top);
+            low.push(bottom);
+            let (top, bottom) = if mora.high {
+                (lines.line, ' ')
+            } else {
+                (' ', lines.line)
+            };
+            high.extend(std::iter::repeat_n(top, width - 1));
+            low.extend(std::iter::repeat_n(bottom, width - 1));
+            kana.push_str(&mora.text);
+            last_high = Some(mora.high);
+        }
+        if word.downstep == Some(word.morae.len()) {
+            high.push(lines.fall_top);
+            low.push(lines.fall_bottom);
+            low.push(lines.line);
+        }
+    }
+    [high, low, kana]
+}
+
+/// Columns `mora` takes in a terminal: two for every full width kana, one for
+/// half width (semi) voiced marks, none for combining marks.
+fn width(mora: &str) -> usize {
+    let width = mora
+        .chars()
+        .map(|c| match c {
+            VOICED | '\u{3099}' | '\u{20dd}' => 0,
+            'ﾞ' | 'ﾟ' => 1,
+            _ => 2,
+        })
+        .sum();
+    usize::max(width, 1)
+}
+
+/// The head as one line, with the brackets it is printed with.
+fn head_text(head: &Head) -> String {
+    match head {
+        Head::H(hs) => {
+            let parts: Vec<String> = hs
+                .iter()
+                .map(|h| match h {
+                    H::Subheadword(_, s) => s.clone(),
+                    H::HW(s, Some((inners, end))) => {
+                        let inner: String = inners.iter().map(Inner::to_string).collect();
+                        format!("{s}{inner}{end}")
+                    }
+                    h => h.to_string(),
+                })
+                .collect();
+            parts.join(" ")
+        }
+        Head::Joshiword(joshiword) => joshiword.1.clone(),
+        Head::Ref(refs) => refs
+            .iter()
+            .map(|r| match r {
+                RefHead::BlackBranckets(s, Some((inner, end))) => format!("{s}{inner}{end}"),
+                RefHead::Refheadword(s)
+                | RefHead::BlackBranckets(s, None)
+                | RefHead::DAngleBrackets(s)
+                | RefHead::RoundBrackets(s)
+                | RefHead::SquareBrackets(s) => s.clone(),
+            })
+            .collect(),
+        Head::None => String::new(),
+    }
+}
+
+fn ref_text(content: &[RefContent]) -> String {
+    content
+        .iter()
+        .map(|c| match c {
+            RefContent::Text(s) | RefContent::RoundBrackets(s) | RefContent::TextSpan(s) => {
+                s.as_str()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixture::{IPPON_SOUND, SAKURA_SOUND, page_xml},
+        try_parse_xml,
+    };
+
+    fn rows(accent: &str, lines: &Lines) -> Vec<String> {
+        graph(&parse_pitch(accent), lines)
+            .into_iter()
+            .map(|row| row.trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn graphs() {
+        assert_eq!(rows("サクラ▔", &BOX_DRAWING), ["  ┌───", "──┘", "サクラ"]);
+        assert_eq!(rows("ア＼メ", &BOX_DRAWING), ["──┐", "  └─", "アメ"]);
+        assert_eq!(rows("ハ＼ナミ", &ASCII), ["--+", "  +---", "ハナミ"]);
+        assert_eq!(
+            rows("イモ＼ート", &BOX_DRAWING),
+            ["  ┌─┐", "──┘ └───", "イモート"]
+        );
+        // odaka drops after the last mora
+        assert_eq!(
+            rows("オトコ＼", &BOX_DRAWING),
+            ["  ┌───┐", "──┘   └─", "オトコ"]
+        );
+        assert_eq!(
+            rows("ジュ＼ー・ゴ＼ニチ", &BOX_DRAWING),
+            ["────┐   ──┐", "    └─    └───", "ジュー・ゴニチ"]
+        );
+        assert_eq!(width("ケ゚"), 2);
+        assert_eq!(width("キョ"), 4);
+    }
+
+    #[test]
+    fn entry() {
+        let renderer = TextRenderer::new();
+        let item = try_parse_xml(&page_xml(1, "さくら")).unwrap();
+        assert_eq!(
+            renderer.render(&item),
+            format!(
+                "さくら 【桜】\n  サクラ▔ [0] ♪{SAKURA_SOUND}\n      ┌───\n    ──┘\n    サクラ\n"
+            )
+        );
+
+        let mut renderer = TextRenderer::new();
+        renderer.set_graph(false);
+        let item = try_parse_xml(&page_xml(3, "―ほん")).unwrap();
+        assert_eq!(
+            renderer.render(&item),
+            format!("―ほん 【―本】\n  ホン▔ [0]\n1\n  イッ＼ポン [2] ♪{IPPON_SOUND}\n")
+        );
+    }
+}
@@ -1,24 +1,84 @@
+mod cache;
 mod nrsc;
+mod nrsc_writer;
 mod rsc;
+mod rsc_writer;
 
-use std::fs;
+use std::cell::RefCell;
 
-pub use nrsc::Nrsc;
+pub use cache::CacheStats;
+pub use nrsc::{Nrsc, NrscFormat};
+pub use nrsc_writer::NrscWriter;
 pub use rsc::Rsc;
+pub use rsc_writer::RscWriter;
 
-use crate::Error;
+use crate::{
+    Error,
+    storage::{Bytes, ReadAt, Storage, join},
+};
 
-use miniz_oxide::inflate::{TINFLStatus as ZStatus, core as zlib};
+use miniz_oxide::{
+    deflate::compress_to_vec_zlib,
+    inflate::{TINFLStatus as ZStatus, core as zlib},
+};
+
+// same trade-off as zlib's default level
+const ZLIB_LEVEL: u8 = 6;
 
-#[derive(Debug)]
 struct ResourceFile {
     seqnum: u32,
     len: usize,
     offset: usize,
-    file: fs::File,
+    file: Box<dyn ReadAt>,
+    map: Option<Bytes>,
+}
+
+impl ResourceFile {
+    /// The files of `dir` that `parse_fname` gives a sequence number, in order.
+    /// `first` is the number of the first file, the numbers can't have gaps.
+    fn open_all(
+        storage: &dyn Storage,
+        dir: &str,
+        first: u32,
+        parse_fname: impl Fn(&str) -> Option<u32>,
+    ) -> Result<Vec<ResourceFile>, Error> {
+        let mut files = Vec::new();
+        for name in storage.list(dir)? {
+            if let Some(seqnum) = parse_fname(&name) {
+                let file = storage.open(&join(dir, &name))?;
+                files.push(ResourceFile {
+                    seqnum,
+                    len: file.len() as usize,
+                    offset: 0,
+                    map: file.bytes(),
+                    file,
+                });
+            }
+        }
+        files.sort_by_key(|f| f.seqnum);
+        let mut offset = 0;
+        for (i, cf) in files.iter_mut().enumerate() {
+            if cf.seqnum != i as u32 + first {
+                return Err(Error::MissingResourceFile);
+            }
+            cf.offset = offset;
+            offset += cf.len;
+        }
+        Ok(files)
+    }
+}
+
+thread_local! {
+    // One decompressor per thread, so resources can be read from several threads at once.
+    static ZLIB_STATE: RefCell<Box<zlib::DecompressorOxide>> =
+        RefCell::new(Box::new(zlib::DecompressorOxide::new()));
 }
 
-fn decompress(
+fn decompress(in_buf: &[u8], out_buf: &mut Vec<u8>) -> Result<usize, Error> {
+    ZLIB_STATE.with_borrow_mut(|zlib_state| decompress_with(zlib_state, in_buf, out_buf))
+}
+
+fn decompress_with(
     zlib_state: &mut zlib::DecompressorOxide,
     in_buf: &[u8],
     out_buf: &mut Vec<u8>,
@@ -54,3 +114,7 @@ fn decompress(
     }
     Ok(n_out_total)
 }
+
+fn compress(in_buf: &[u8]) -> Vec<u8> {
+    compress_to_vec_zlib(in_buf, ZLIB_LEVEL)
+}
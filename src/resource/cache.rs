@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+pub(crate) const DEFAULT_CACHE_CAPACITY: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Decompressed content blocks keyed by their zoffset.
+/// Least recently used blocks are evicted once `capacity` bytes are exceeded,
+/// but the most recent block is always kept.
+#[derive(Debug)]
+pub(crate) struct BlockCache {
+    capacity: usize,
+    size: usize,
+    // most recently used block first
+    blocks: VecDeque<(usize, Vec<u8>)>,
+    // buffer of the last evicted block, reused for the next one
+    spare: Option<Vec<u8>>,
+    stats: CacheStats,
+}
+
+impl BlockCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        BlockCache {
+            capacity,
+            size: 0,
+            blocks: VecDeque::new(),
+            spare: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Moves the block to the front if it is cached and counts the hit or miss.
+    pub(crate) fn lookup(&mut self, zoffset: usize) -> bool {
+        let Some(pos) = self.blocks.iter().position(|(z, _)| *z == zoffset) else {
+            self.stats.misses += 1;
+            return false;
+        };
+        if pos != 0 {
+            let block = self.blocks.remove(pos).unwrap();
+            self.blocks.push_front(block);
+        }
+        self.stats.hits += 1;
+        true
+    }
+
+    /// The most recently used block. Panics if the cache is empty.
+    pub(crate) fn front(&self) -> &[u8] {
+        &self.blocks[0].1
+    }
+
+    pub(crate) fn spare_buffer(&mut self) -> Vec<u8> {
+        self.spare.take().unwrap_or_default()
+    }
+
+    /// Adds a block as the most recently used one. If another reader inserted
+    /// the same block in the meantime, that one is kept.
+    pub(crate) fn insert(&mut self, zoffset: usize, block: Vec<u8>) {
+        if let Some(pos) = self.blocks.iter().position(|(z, _)| *z == zoffset) {
+            let cached = self.blocks.remove(pos).unwrap();
+            self.blocks.push_front(cached);
+            self.spare = Some(block);
+            return;
+        }
+        self.size += block.len();
+        self.blocks.push_front((zoffset, block));
+        self.evict();
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity && self.blocks.len() > 1 {
+            let (_, block) = self.blocks.pop_back().unwrap();
+            self.size -= block.len();
+            self.spare = Some(block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_order() {
+        let mut cache = BlockCache::new(20);
+        assert!(!cache.lookup(0));
+        cache.insert(0, vec![0; 10]);
+        assert!(!cache.lookup(100));
+        cache.insert(100, vec![1; 10]);
+        assert!(cache.lookup(0));
+        assert_eq!(cache.front(), [0; 10]);
+
+        // 100 is the least recently used block now
+        assert!(!cache.lookup(200));
+        cache.insert(200, vec![2; 10]);
+        assert!(!cache.lookup(100));
+        assert!(cache.lookup(0));
+        assert!(cache.lookup(200));
+        assert_eq!(cache.spare_buffer(), [1; 10]);
+        assert!(cache.spare_buffer().is_empty());
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 4 });
+    }
+
+    #[test]
+    fn keeps_one_block() {
+        let mut cache = BlockCache::new(0);
+        cache.insert(0, vec![0; 10]);
+        assert!(cache.lookup(0));
+        cache.insert(100, vec![1; 10]);
+        assert!(!cache.lookup(0));
+        assert_eq!(cache.front(), [1; 10]);
+
+        cache.set_capacity(100);
+        cache.insert(0, vec![0; 10]);
+        cache.set_capacity(10);
+        assert!(cache.lookup(0));
+        assert!(!cache.lookup(100));
+    }
+
+    #[test]
+    fn insert_twice() {
+        let mut cache = BlockCache::new(100);
+        cache.insert(0, vec![0; 10]);
+        cache.insert(100, vec![1; 10]);
+        cache.insert(0, vec![2; 10]);
+        assert_eq!(cache.front(), [0; 10]);
+        assert_eq!(cache.spare_buffer(), [2; 10]);
+    }
+}
@@ -1,24 +1,22 @@
 use core::mem::size_of;
-use std::{
-    ffi::OsStr,
-    fs::{self, File},
-    io::{Read, Seek, SeekFrom},
-    path::Path,
-};
-
-use miniz_oxide::inflate::core as zlib;
+use std::{borrow::Cow, str::from_utf8};
 
-use crate::{Error, abi_utils::TransmuteSafe, resource::decompress};
+use crate::{
+    Error,
+    abi_utils::{Region, TransmuteSafe},
+    resource::decompress,
+    storage::{Storage, join},
+};
 
 #[derive(Debug, Clone)]
 pub(crate) struct NrscIndex {
-    idx: Vec<NrscIdxRecord>,
-    ids: String, // contains null bytes as substring separators
+    idx: Region<NrscIdxRecord>,
+    ids: Region<u8>, // contains null bytes as substring separators
 }
 
 mod abi {
 
-    use super::Format;
+    use super::NrscFormat;
     use crate::Error;
 
     // TODO: Use LE16 & LE32?
@@ -33,14 +31,34 @@ mod abi {
     }
 
     impl NrscIdxRecord {
+        pub(crate) fn new(
+            format: NrscFormat,
+            fileseq: u16,
+            id_str_offset: u32,
+            file_offset: u32,
+            len: u32,
+        ) -> Self {
+            let format = match format {
+                NrscFormat::Uncompressed => 0,
+                NrscFormat::Zlib => 1,
+            };
+            NrscIdxRecord {
+                format: u16::to_le(format),
+                fileseq: u16::to_le(fileseq),
+                id_str_offset: u32::to_le(id_str_offset),
+                file_offset: u32::to_le(file_offset),
+                len: u32::to_le(len),
+            }
+        }
+
         pub fn id_str_offset(&self) -> usize {
             u32::from_le(self.id_str_offset) as usize
         }
 
-        pub(super) fn format(&self) -> Result<Format, Error> {
+        pub(super) fn format(&self) -> Result<NrscFormat, Error> {
             match u16::from_le(self.format) {
-                0 => Ok(Format::Uncompressed),
-                1 => Ok(Format::Zlib),
+                0 => Ok(NrscFormat::Uncompressed),
+                1 => Ok(NrscFormat::Zlib),
                 _ => Err(Error::InvalidAudioFormat),
             }
         }
@@ -69,17 +87,18 @@ mod abi {
             file_offset: 0,
             len: 0,
         };
+        let offsets = [0, 1, 3, 6, 10];
+        let diff = 8 + offsets.len() * size_of::<NrscIdxRecord>();
         let mut audio_idx = NrscIndex {
-            idx: vec![air(0), air(1), air(3), air(6), air(10)],
-            ids: "\0a\0bb\0ccc\0dddd".to_owned(),
+            // Offsets are relative to the start of the file
+            idx: offsets
+                .iter()
+                .map(|o| air(o + diff as u32))
+                .collect::<Vec<_>>()
+                .into(),
+            ids: b"\0a\0bb\0ccc\0dddd".to_vec().into(),
         };
 
-        let diff = 8 + audio_idx.idx.len() * size_of::<NrscIdxRecord>();
-        // Fix offsets now that they are known
-        for air in audio_idx.idx.iter_mut() {
-            air.id_str_offset += diff as u32;
-        }
-
         assert_eq!(audio_idx.get_id_at(diff + 0).unwrap(), "");
         assert_eq!(audio_idx.get_id_at(diff + 1).unwrap(), "a");
         assert_eq!(audio_idx.get_id_at(diff + 3).unwrap(), "bb");
@@ -87,7 +106,7 @@ mod abi {
         assert_eq!(audio_idx.get_id_at(diff + 6).unwrap(), "ccc");
         assert_eq!(audio_idx.get_id_at(diff + 10), Err(Error::InvalidIndex));
 
-        audio_idx.ids = "\0a\0bb\0ccc\0dddd\0".to_owned();
+        audio_idx.ids = b"\0a\0bb\0ccc\0dddd\0".to_vec().into();
         let diff = diff as u32;
         assert_eq!(audio_idx.get_by_id("").unwrap(), air(diff + 0));
         assert_eq!(audio_idx.get_by_id("a").unwrap(), air(diff + 1));
@@ -102,7 +121,9 @@ pub(crate) use abi::NrscIdxRecord;
 
 use super::ResourceFile;
 
-enum Format {
+/// How a blob is stored in an nrsc file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NrscFormat {
     Uncompressed,
     Zlib,
 }
@@ -110,30 +131,44 @@ enum Format {
 unsafe impl TransmuteSafe for NrscIdxRecord {}
 
 impl NrscIndex {
-    pub(crate) fn new(path: &Path) -> Result<Self, Error> {
-        let path = path.join("index.nidx");
-        let mut file = File::open(path).map_err(|_| Error::FopenError)?;
+    pub(crate) fn new(storage: &dyn Storage, dir: &str) -> Result<Self, Error> {
+        let file = storage
+            .open(&join(dir, "index.nidx"))
+            .map_err(|_| Error::FopenError)?;
         let mut len = [0; 8];
-        file.read_exact(&mut len).map_err(|_| Error::IOError)?;
+        file.read_exact_at(&mut len, 0)?;
         let len = u32::from_le_bytes(len[4..8].try_into().unwrap()) as usize;
-        let file_size = file.metadata().map_err(|_| Error::IOError)?.len() as usize;
+        let file_size = file.len() as usize;
         let idx_expected_size = size_of::<NrscIdxRecord>() * len + 8;
+        if file_size < idx_expected_size {
+            return Err(Error::IncorrectStreamLength);
+        }
+        if let Some(bytes) = file.bytes() {
+            let idx = Region::from_map(&bytes, 8, len)?;
+            let ids = Region::from_map(&bytes, idx_expected_size, file_size - idx_expected_size)?;
+            return Ok(Self { idx, ids });
+        }
         let mut idx = vec![NrscIdxRecord::default(); len];
-        let mut ids = String::with_capacity(file_size - idx_expected_size);
-        file.read_exact(NrscIdxRecord::slice_as_bytes_mut(idx.as_mut_slice()))
-            .map_err(|_| Error::IOError)?;
-        file.read_to_string(&mut ids).map_err(|_| Error::IOError)?;
-        Ok(Self { idx, ids })
+        let mut ids = vec![0; file_size - idx_expected_size];
+        file.read_exact_at(NrscIdxRecord::slice_as_bytes_mut(idx.as_mut_slice()), 8)?;
+        file.read_exact_at(&mut ids, idx_expected_size as u64)?;
+        Ok(Self {
+            idx: idx.into(),
+            ids: ids.into(),
+        })
     }
 
     fn get_id_at(&self, offset: usize) -> Result<&str, Error> {
         let offset = offset - (size_of::<NrscIdxRecord>() * self.idx.len() + 8);
-        if offset > 0 && &self.ids[offset - 1..offset] != "\0" {
+        if offset > 0 && self.ids.get(offset - 1) != Some(&b'\0') {
             return Err(Error::InvalidIndex);
         }
-        let tail = &self.ids[offset..];
-        let len = tail.find('\0').ok_or(Error::InvalidIndex)?;
-        Ok(&tail[..len])
+        let tail = self.ids.get(offset..).ok_or(Error::InvalidIndex)?;
+        let len = tail
+            .iter()
+            .position(|b| *b == b'\0')
+            .ok_or(Error::InvalidIndex)?;
+        Ok(from_utf8(&tail[..len])?)
     }
 
     pub fn get_by_id(&self, id: &str) -> Result<NrscIdxRecord, Error> {
@@ -167,14 +202,10 @@ pub struct Nrsc {
 
 struct NrscData {
     files: Vec<ResourceFile>,
-    read_buf: Vec<u8>,
-    decomp_buf: Vec<u8>,
-    zlib_state: zlib::DecompressorOxide,
 }
 
 impl Nrsc {
-    fn parse_fname(fname: &OsStr) -> Option<u32> {
-        let fname = fname.to_str()?;
+    fn parse_fname(fname: &str) -> Option<u32> {
         if fname.ends_with(".nrsc") {
             let secnum_end = fname.len() - ".nrsc".len();
             fname[..secnum_end].parse().ok()
@@ -183,54 +214,23 @@ impl Nrsc {
         }
     }
 
-    fn files(path: &Path) -> Result<Vec<ResourceFile>, Error> {
-        let mut files = Vec::new();
-
-        for entry in fs::read_dir(path).map_err(|_| Error::IOError)? {
-            let entry = entry.map_err(|_| Error::IOError)?;
-            let seqnum = Nrsc::parse_fname(&entry.file_name());
-            if let Some(seqnum) = seqnum {
-                files.push(ResourceFile {
-                    seqnum,
-                    len: entry.metadata().map_err(|_| Error::IOError)?.len() as usize,
-                    offset: 0,
-                    file: File::open(entry.path()).map_err(|_| Error::IOError)?,
-                });
-            }
-        }
-        let mut offset = 0;
-        files.sort_by_key(|f| f.seqnum);
-        for (i, cf) in files.iter_mut().enumerate() {
-            if cf.seqnum != i as u32 {
-                return Err(Error::MissingResourceFile);
-            }
-            cf.offset = offset;
-            offset += cf.len;
-        }
-        Ok(files)
-    }
-
-    pub(crate) fn new(path: &Path) -> Result<Self, Error> {
-        let files = Nrsc::files(path)?;
-        let index = NrscIndex::new(path)?;
+    /// The resource in the directory `dir` of `storage`.
+    pub(crate) fn new(storage: &dyn Storage, dir: &str) -> Result<Self, Error> {
+        let files = ResourceFile::open_all(storage, dir, 0, Nrsc::parse_fname)?;
+        let index = NrscIndex::new(storage, dir)?;
         Ok(Nrsc {
             index,
-            data: NrscData {
-                files,
-                read_buf: Vec::new(),
-                decomp_buf: Vec::new(),
-                zlib_state: zlib::DecompressorOxide::new(),
-            },
+            data: NrscData { files },
         })
     }
 
-    pub fn get_by_idx(&mut self, idx: usize) -> Result<(&str, &[u8]), Error> {
+    pub fn get_by_idx(&self, idx: usize) -> Result<(&str, Cow<'_, [u8]>), Error> {
         let (id, nidx_rec) = self.index.get_by_idx(idx)?;
         let item = self.data.get_by_nidx_rec(nidx_rec)?;
         Ok((id, item))
     }
 
-    pub fn get(&mut self, id: &str) -> Result<&[u8], Error> {
+    pub fn get(&self, id: &str) -> Result<Cow<'_, [u8]>, Error> {
         self.data.get_by_nidx_rec(self.index.get_by_id(id)?)
     }
 
@@ -240,28 +240,29 @@ impl Nrsc {
 }
 
 impl NrscData {
-    fn get_by_nidx_rec(&mut self, idx: NrscIdxRecord) -> Result<&[u8], Error> {
-        let file = &mut self.files[idx.fileseq()];
-
-        file.file
-            .seek(SeekFrom::Start(idx.file_offset()))
-            .map_err(|_| Error::IOError)?;
-        if self.read_buf.len() < idx.len() {
-            self.read_buf.resize(idx.len(), 0);
-        }
-        file.file
-            .read_exact(&mut self.read_buf[..idx.len()])
-            .map_err(|_| Error::IOError)?;
+    fn get_by_nidx_rec(&self, idx: NrscIdxRecord) -> Result<Cow<'_, [u8]>, Error> {
+        let file = self
+            .files
+            .get(idx.fileseq())
+            .ok_or(Error::MissingResourceFile)?;
+
+        let data = if let Some(map) = &file.map {
+            let map: &[u8] = (**map).as_ref();
+            let start = idx.file_offset() as usize;
+            Cow::Borrowed(map.get(start..start + idx.len()).ok_or(Error::IOError)?)
+        } else {
+            let mut read_buf = vec![0; idx.len()];
+            file.file.read_exact_at(&mut read_buf, idx.file_offset())?;
+            Cow::Owned(read_buf)
+        };
 
         match idx.format()? {
-            Format::Uncompressed => Ok(&self.read_buf[..idx.len()]),
-            Format::Zlib => {
-                let n_out = decompress(
-                    &mut self.zlib_state,
-                    &self.read_buf[..idx.len()],
-                    &mut self.decomp_buf,
-                )?;
-                Ok(&self.decomp_buf[..n_out])
+            NrscFormat::Uncompressed => Ok(data),
+            NrscFormat::Zlib => {
+                let mut decomp_buf = Vec::new();
+                let n_out = decompress(&data, &mut decomp_buf)?;
+                decomp_buf.truncate(n_out);
+                Ok(Cow::Owned(decomp_buf))
             }
         }
     }
@@ -0,0 +1,187 @@
+use core::mem::size_of;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Error,
+    abi_utils::TransmuteSafe,
+    resource::{
+        compress,
+        nrsc::{NrscFormat, NrscIdxRecord},
+    },
+};
+
+const DEFAULT_FILE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Writes `NNNNN.nrsc` archives and the `index.nidx` that `Nrsc` reads.
+///
+/// Blobs are appended in the order they are added, a new archive is started before
+/// a blob would make the current one larger than `file_size`. The index is sorted
+/// by id when the writer is finished.
+#[derive(Debug)]
+pub struct NrscWriter {
+    dir: PathBuf,
+    file_size: usize,
+    // id, format, fileseq, file offset, stored length
+    entries: Vec<(String, NrscFormat, u16, u32, u32)>,
+    file: Option<BufWriter<File>>,
+    fileseq: u16,
+    file_len: usize,
+}
+
+impl NrscWriter {
+    pub fn new(dir: &Path) -> Self {
+        NrscWriter {
+            dir: dir.to_path_buf(),
+            file_size: DEFAULT_FILE_SIZE,
+            entries: Vec::new(),
+            file: None,
+            fileseq: 0,
+            file_len: 0,
+        }
+    }
+
+    /// Size an archive may grow to. An archive always holds at least one blob.
+    pub fn set_file_size(&mut self, bytes: usize) {
+        self.file_size = bytes;
+    }
+
+    /// Adds a blob, stored as is or zlib compressed depending on `format`.
+    pub fn add(&mut self, id: &str, data: &[u8], format: NrscFormat) -> Result<(), Error> {
+        if id.contains('\0') {
+            return Err(Error::InvalidArg);
+        }
+        let compressed;
+        let data = match format {
+            NrscFormat::Uncompressed => data,
+            NrscFormat::Zlib => {
+                compressed = compress(data);
+                &compressed
+            }
+        };
+        let len = u32::try_from(data.len()).map_err(|_| Error::RecordTooLarge)?;
+
+        if self.file.is_none() || (self.file_len > 0 && self.file_len + data.len() > self.file_size)
+        {
+            self.next_file()?;
+        }
+        let file_offset = u32::try_from(self.file_len).map_err(|_| Error::RecordTooLarge)?;
+        let file = self.file.as_mut().expect("an archive was just opened");
+        file.write_all(data)?;
+        self.file_len += data.len();
+
+        let fileseq = self.fileseq - 1;
+        self.entries
+            .push((id.to_string(), format, fileseq, file_offset, len));
+        Ok(())
+    }
+
+    /// Flushes the last archive and writes `index.nidx`. Fails on duplicate ids.
+    pub fn finish(mut self) -> Result<(), Error> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        // same order as `str::cmp`, which `NrscIndex::get_by_id` searches with
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+        if self.entries.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(Error::InvalidArg);
+        }
+
+        // id offsets are counted from the start of the index file
+        let ids_start = 8 + self.entries.len() * size_of::<NrscIdxRecord>();
+        let mut ids = Vec::new();
+        let mut records = Vec::with_capacity(self.entries.len());
+        for (id, format, fileseq, file_offset, len) in &self.entries {
+            let id_str_offset =
+                u32::try_from(ids_start + ids.len()).map_err(|_| Error::RecordTooLarge)?;
+            records.push(NrscIdxRecord::new(
+                *format,
+                *fileseq,
+                id_str_offset,
+                *file_offset,
+                *len,
+            ));
+            ids.extend_from_slice(id.as_bytes());
+            ids.push(b'\0');
+        }
+
+        let mut nidx = BufWriter::new(File::create(self.dir.join("index.nidx"))?);
+        nidx.write_all(&[0; 4])?;
+        nidx.write_all(&(records.len() as u32).to_le_bytes())?;
+        nidx.write_all(NrscIdxRecord::slice_as_bytes(&records))?;
+        nidx.write_all(&ids)?;
+        nidx.flush()?;
+        Ok(())
+    }
+
+    fn next_file(&mut self) -> Result<(), Error> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        let name = format!("{:05}.nrsc", self.fileseq);
+        self.file = Some(BufWriter::new(File::create(self.dir.join(name))?));
+        self.fileseq = self.fileseq.checked_add(1).ok_or(Error::RecordTooLarge)?;
+        self.file_len = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DirStorage, LoadMode, fixture::temp_dir, resource::Nrsc};
+
+    fn blob(i: usize) -> Vec<u8> {
+        (0..i * 7).map(|b| (b % 13) as u8).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_dir("nrsc_writer");
+        // added out of order, the index has to be sorted anyway
+        let ids = ["20", "3", "100", "ab", "", "a", "日本"];
+        let mut writer = NrscWriter::new(&dir);
+        writer.set_file_size(40);
+        for (i, id) in ids.iter().enumerate() {
+            let format = if i % 2 == 0 {
+                NrscFormat::Zlib
+            } else {
+                NrscFormat::Uncompressed
+            };
+            writer.add(id, &blob(i), format).unwrap();
+        }
+        writer.finish().unwrap();
+        assert!(dir.join("00001.nrsc").exists());
+
+        for mode in [LoadMode::Mmap, LoadMode::Read] {
+            let nrsc = Nrsc::new(&DirStorage::with_mode(&dir, mode), "").unwrap();
+            assert_eq!(nrsc.len(), ids.len());
+            for (i, id) in ids.iter().enumerate() {
+                assert_eq!(*nrsc.get(id).unwrap(), blob(i));
+            }
+            assert_eq!(nrsc.get("b"), Err(Error::NotFound));
+
+            let mut sorted = ids;
+            sorted.sort();
+            for (idx, id) in sorted.iter().enumerate() {
+                assert_eq!(nrsc.get_by_idx(idx).unwrap().0, *id);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_ids() {
+        let dir = temp_dir("nrsc_writer_dup");
+        let mut writer = NrscWriter::new(&dir);
+        assert_eq!(
+            writer.add("a\0b", b"", NrscFormat::Uncompressed),
+            Err(Error::InvalidArg)
+        );
+        writer.add("a", b"1", NrscFormat::Uncompressed).unwrap();
+        writer.add("a", b"2", NrscFormat::Zlib).unwrap();
+        assert_eq!(writer.finish(), Err(Error::InvalidArg));
+    }
+}
@@ -1,16 +1,17 @@
 use core::{cmp::min, mem::size_of, ops::Not, slice};
-use miniz_oxide::inflate::core as zlib;
 use std::{
-    ffi::OsStr,
-    fs::{self, File},
-    io::{Read, Seek, SeekFrom},
-    path::Path,
+    borrow::Cow,
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
 use crate::{
     Error,
-    abi_utils::{LE32, TransmuteSafe},
-    resource::decompress,
+    abi_utils::{LE32, Region, TransmuteSafe},
+    resource::{
+        cache::{BlockCache, CacheStats, DEFAULT_CACHE_CAPACITY},
+        decompress,
+    },
+    storage::{Storage, join},
 };
 
 mod abi {
@@ -50,8 +51,8 @@ mod abi {
 
         assert_eq!(
             RscIndex {
-                idx: Some(vec![]),
-                map: vec![],
+                idx: Some(vec![].into()),
+                map: vec![].into(),
             }
             .get_by_id(500),
             Err(Error::NotFound)
@@ -59,8 +60,8 @@ mod abi {
 
         assert_eq!(
             RscIndex {
-                idx: Some(vec![idx(1, 0)]),
-                map: vec![map(0, 0)],
+                idx: Some(vec![idx(1, 0)].into()),
+                map: vec![map(0, 0)].into(),
             }
             .get_by_id(500),
             Err(Error::NotFound)
@@ -68,8 +69,8 @@ mod abi {
 
         assert_eq!(
             RscIndex {
-                idx: Some(vec![idx(1, 0), idx(2, 1)]),
-                map: vec![map(0, 0), map(0, 10)],
+                idx: Some(vec![idx(1, 0), idx(2, 1)].into()),
+                map: vec![map(0, 0), map(0, 10)].into(),
             }
             .get_by_id(500),
             Err(Error::NotFound)
@@ -77,8 +78,8 @@ mod abi {
 
         assert_eq!(
             RscIndex {
-                idx: Some(vec![idx(1, 0), idx(2, 1), idx(1000, 2)]),
-                map: vec![map(0, 0), map(0, 10), map(0, 20)],
+                idx: Some(vec![idx(1, 0), idx(2, 1), idx(1000, 2)].into()),
+                map: vec![map(0, 0), map(0, 10), map(0, 20)].into(),
             }
             .get_by_id(500),
             Err(Error::NotFound)
@@ -86,8 +87,8 @@ mod abi {
 
         assert_eq!(
             RscIndex {
-                idx: Some(vec![idx(1, 0), idx(2, 1), idx(500, 2), idx(1000, 3)]),
-                map: vec![map(0, 0), map(0, 10), map(0, 20), map(10, 0)],
+                idx: Some(vec![idx(1, 0), idx(2, 1), idx(500, 2), idx(1000, 3)].into()),
+                map: vec![map(0, 0), map(0, 10), map(0, 20), map(10, 0)].into(),
             }
             .get_by_id(500),
             Ok(map(0, 20))
@@ -95,14 +96,17 @@ mod abi {
 
         assert_eq!(
             RscIndex {
-                idx: Some(vec![
-                    idx(1, 0),
-                    idx(2, 1),
-                    idx(499, 2),
-                    idx(500, 3),
-                    idx(501, 4),
-                    idx(1000, 5)
-                ]),
+                idx: Some(
+                    vec![
+                        idx(1, 0),
+                        idx(2, 1),
+                        idx(499, 2),
+                        idx(500, 3),
+                        idx(501, 4),
+                        idx(1000, 5)
+                    ]
+                    .into()
+                ),
                 map: vec![
                     map(0, 0),
                     map(0, 10),
@@ -110,7 +114,8 @@ mod abi {
                     map(10, 0),
                     map(10, 0),
                     map(10, 0)
-                ],
+                ]
+                .into(),
             }
             .get_by_id(500),
             Ok(map(10, 0))
@@ -123,59 +128,56 @@ use super::ResourceFile;
 
 #[derive(Debug, Clone)]
 pub(crate) struct RscIndex {
-    idx: Option<Vec<IdxRecord>>,
-    map: Vec<MapRecord>,
+    idx: Option<Region<IdxRecord>>,
+    map: Region<MapRecord>,
 }
 
 unsafe impl TransmuteSafe for MapRecord {}
 unsafe impl TransmuteSafe for IdxRecord {}
 
 impl RscIndex {
-    fn load_idx(path: &Path) -> Result<Option<Vec<IdxRecord>>, Error> {
-        let path = path.with_extension("idx");
-        if path.exists().not() {
+    fn load_idx(storage: &dyn Storage, stem: &str) -> Result<Option<Region<IdxRecord>>, Error> {
+        let path = format!("{stem}.idx");
+        if storage.exists(&path).not() {
             return Ok(None);
         };
-        let mut idx_file = File::open(path)?;
+        let idx_file = storage.open(&path)?;
         let mut len = [0; 4];
-        idx_file.read_exact(&mut len)?;
+        idx_file.read_exact_at(&mut len, 0)?;
         let len = u32::from_le_bytes(len) as usize;
-        idx_file.seek(SeekFrom::Start(8))?;
-        let idx_size = idx_file.metadata().map_err(|_| Error::IOError)?.len();
         let idx_expected_size = (size_of::<IdxRecord>() * len + 8) as u64;
-        if idx_size != idx_expected_size {
+        if idx_file.len() != idx_expected_size {
             return Err(Error::IncorrectStreamLength);
         }
+        if let Some(bytes) = idx_file.bytes() {
+            return Region::from_map(&bytes, 8, len).map(Some);
+        }
         let mut idx = vec![IdxRecord::default(); len];
-        idx_file
-            .read_exact(IdxRecord::slice_as_bytes_mut(idx.as_mut_slice()))
-            .map_err(|_| Error::IOError)?;
-        Ok(Some(idx))
+        idx_file.read_exact_at(IdxRecord::slice_as_bytes_mut(idx.as_mut_slice()), 8)?;
+        Ok(Some(idx.into()))
     }
 
-    fn load_map(path: &Path) -> Result<Vec<MapRecord>, Error> {
-        let path = path.with_extension("map");
-        let mut map_file = File::open(path)?;
+    fn load_map(storage: &dyn Storage, stem: &str) -> Result<Region<MapRecord>, Error> {
+        let map_file = storage.open(&format!("{stem}.map"))?;
         let mut len = [0; 4];
-        map_file.seek(SeekFrom::Start(4))?;
-        map_file.read_exact(&mut len)?;
+        map_file.read_exact_at(&mut len, 4)?;
         let len = u32::from_le_bytes(len) as usize;
-        map_file.seek(SeekFrom::Start(8))?;
-        let map_size = map_file.metadata().map_err(|_| Error::IOError)?.len();
         let map_expected_size = (size_of::<MapRecord>() * len + 8) as u64;
-        if map_size != map_expected_size {
+        if map_file.len() != map_expected_size {
             return Err(Error::IncorrectStreamLength);
         }
+        if let Some(bytes) = map_file.bytes() {
+            return Region::from_map(&bytes, 8, len);
+        }
         let mut map = vec![MapRecord::default(); len];
-        map_file
-            .read_exact(MapRecord::slice_as_bytes_mut(map.as_mut_slice()))
-            .map_err(|_| Error::IOError)?;
-        Ok(map)
+        map_file.read_exact_at(MapRecord::slice_as_bytes_mut(map.as_mut_slice()), 8)?;
+        Ok(map.into())
     }
-    pub(crate) fn new(path: &Path, rsc_name: &str) -> Result<Self, Error> {
-        let path = path.join(rsc_name); // filename stem
-        let idx = Self::load_idx(&path)?;
-        let map = Self::load_map(&path)?;
+
+    pub(crate) fn new(storage: &dyn Storage, dir: &str, rsc_name: &str) -> Result<Self, Error> {
+        let stem = join(dir, rsc_name);
+        let idx = Self::load_idx(storage, &stem)?;
+        let map = Self::load_map(storage, &stem)?;
         Ok(RscIndex { idx, map })
     }
 
@@ -232,16 +234,11 @@ impl RscIndex {
 pub struct Rsc {
     index: RscIndex,
     files: Vec<ResourceFile>,
-    zlib_buf: Vec<u8>,
-    zlib_state: zlib::DecompressorOxide,
-    contents_buf: Vec<u8>,
-    current_offset: usize,
-    current_len: usize,
+    cache: Mutex<BlockCache>,
 }
 
 impl Rsc {
-    fn parse_fname(rsc_name: &str, fname: &OsStr) -> Option<u32> {
-        let fname = fname.to_str()?;
+    fn parse_fname(rsc_name: &str, fname: &str) -> Option<u32> {
         let ext = ".rsc";
         let min_len = rsc_name.len() + 1 + ext.len();
         if fname.starts_with(rsc_name) && fname.ends_with(ext) && fname.len() > min_len {
@@ -253,125 +250,119 @@ impl Rsc {
         }
     }
 
-    fn files(path: &Path, rsc_name: &str) -> Result<Vec<ResourceFile>, Error> {
-        let mut files = Vec::new();
-
-        for entry in fs::read_dir(path).map_err(|_| Error::IOError)? {
-            let entry = entry.map_err(|_| Error::IOError)?;
-            let seqnum = Self::parse_fname(rsc_name, &entry.file_name());
-            if let Some(seqnum) = seqnum {
-                files.push(ResourceFile {
-                    seqnum,
-                    len: entry.metadata().map_err(|_| Error::IOError)?.len() as usize,
-                    offset: 0,
-                    file: File::open(entry.path()).map_err(|_| Error::IOError)?,
-                });
-            }
-        }
-        files.sort_by_key(|f| f.seqnum);
-        let mut offset = 0;
-        for (i, cf) in files.iter_mut().enumerate() {
-            if cf.seqnum != i as u32 + 1 {
-                return Err(Error::MissingResourceFile);
-            }
-            cf.offset = offset;
-            offset += cf.len;
-        }
-        Ok(files)
-    }
-
-    pub(crate) fn new(path: &Path, rsc_name: &str) -> Result<Self, Error> {
-        let files = Rsc::files(path, rsc_name)?;
-        let index = RscIndex::new(path, rsc_name)?;
+    /// The resource `rsc_name` in the directory `dir` of `storage`.
+    pub(crate) fn new(storage: &dyn Storage, dir: &str, rsc_name: &str) -> Result<Self, Error> {
+        let files =
+            ResourceFile::open_all(storage, dir, 1, |fname| Self::parse_fname(rsc_name, fname))?;
+        let index = RscIndex::new(storage, dir, rsc_name)?;
         Ok(Self {
             index,
             files,
-            zlib_buf: Vec::new(),
-            zlib_state: zlib::DecompressorOxide::new(),
-            contents_buf: Vec::new(),
-            current_offset: 0,
-            current_len: 0,
+            cache: Mutex::new(BlockCache::new(DEFAULT_CACHE_CAPACITY)),
         })
     }
 
-    fn load_contents(&mut self, zoffset: usize) -> Result<(), Error> {
-        let (file, file_offset) = file_offset(&mut self.files, zoffset)?;
+    fn load_contents(&self, zoffset: usize, contents: &mut Vec<u8>) -> Result<usize, Error> {
+        let (file_idx, file_offset) = file_index(&self.files, zoffset)?;
+        let cf = &self.files[file_idx];
+        if let Some(map) = &cf.map {
+            let map: &[u8] = (**map).as_ref();
+            let block = map.get(file_offset as usize..).ok_or(Error::IOError)?;
+            let (len, block) = LE32::from(block)?;
+            let block = block.get(..len.us()).ok_or(Error::IOError)?;
+            return decompress(block, contents);
+        }
 
         let mut len = [0_u8; 4];
-        file.seek(SeekFrom::Start(file_offset))
-            .map_err(|_| Error::IOError)?;
-        file.read_exact(&mut len).map_err(|_| Error::IOError)?;
+        cf.file.read_exact_at(&mut len, file_offset)?;
         let len = u32::from_le_bytes(len) as usize;
-        if self.zlib_buf.len() < len {
-            self.zlib_buf.resize(len, 0);
-        }
-        file.read_exact(&mut self.zlib_buf[..len])
-            .map_err(|_| Error::IOError)?;
-
-        let n_out = decompress(
-            &mut self.zlib_state,
-            &self.zlib_buf[..len],
-            &mut self.contents_buf,
-        )?;
+        let mut zlib_buf = vec![0; len];
+        cf.file
+            .read_exact_at(&mut zlib_buf, file_offset + size_of::<LE32>() as u64)?;
 
-        self.current_len = n_out;
-        self.current_offset = zoffset;
+        decompress(&zlib_buf, contents)
+    }
 
-        Ok(())
+    fn lock_cache(&self) -> MutexGuard<'_, BlockCache> {
+        // the cache stays consistent even if a reader panicked
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    pub fn get(&mut self, id: u32) -> Result<&[u8], Error> {
+    pub fn get(&self, id: u32) -> Result<Cow<'_, [u8]>, Error> {
         self.get_by_map(self.index.get_by_id(id)?)
     }
 
-    pub fn get_by_idx(&mut self, idx: usize) -> Result<(u32, &[u8]), Error> {
+    pub fn get_by_idx(&self, idx: usize) -> Result<(u32, Cow<'_, [u8]>), Error> {
         let (id, map_rec) = self.index.get_by_idx(idx)?;
         let item = self.get_by_map(map_rec)?;
         Ok((id, item))
     }
 
-    fn get_by_map(&mut self, idx: MapRecord) -> Result<&[u8], Error> {
-        if self.contents_buf.is_empty() || idx.zoffset.us() != self.current_offset {
-            self.load_contents(idx.zoffset.us())?;
+    fn get_by_map(&self, idx: MapRecord) -> Result<Cow<'_, [u8]>, Error> {
+        let zoffset = idx.zoffset.us();
+        let mut cache = self.lock_cache();
+        if !cache.lookup(zoffset) {
+            let mut contents = cache.spare_buffer();
+            // don't block other readers while decompressing
+            drop(cache);
+            let n_out = self.load_contents(zoffset, &mut contents)?;
+            contents.truncate(n_out);
+            cache = self.lock_cache();
+            cache.insert(zoffset, contents);
         }
 
-        let contents = &self.contents_buf[idx.ioffset.us()..self.current_len];
+        let contents = cache
+            .front()
+            .get(idx.ioffset.us()..)
+            .ok_or(Error::InvalidIndex)?;
         let (len, contents_tail) = LE32::from(contents)?;
-        Ok(&contents_tail[..len.us()])
+        let item = contents_tail.get(..len.us()).ok_or(Error::InvalidIndex)?;
+        Ok(Cow::Owned(item.to_vec()))
     }
 
     pub fn len(&self) -> usize {
         self.index.map.len()
     }
+
+    /// Upper bound in bytes for the decompressed blocks kept in memory.
+    pub fn set_cache_capacity(&self, bytes: usize) {
+        self.lock_cache().set_capacity(bytes);
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.lock_cache().stats()
+    }
 }
 
-fn file_offset(contents: &mut [ResourceFile], offset: usize) -> Result<(&mut File, u64), Error> {
+fn file_index(contents: &[ResourceFile], offset: usize) -> Result<(usize, u64), Error> {
     let file_idx = contents
         .binary_search_by(|cf| cmp_range(offset, cf.offset..cf.offset + cf.len).reverse())
         .map_err(|_| Error::InvalidIndex)?;
-    let cf = &mut contents[file_idx];
-    let file = &mut cf.file;
-    let file_offset = (offset - cf.offset) as u64;
-    Ok((file, file_offset))
+    let file_offset = (offset - contents[file_idx].offset) as u64;
+    Ok((file_idx, file_offset))
+}
+
+/// The sequence number of the file `offset` is in, and the offset in that file.
+#[cfg(test)]
+fn file_offset(contents: &[ResourceFile], offset: usize) -> Result<(u32, u64), Error> {
+    let (file_idx, file_offset) = file_index(contents, offset)?;
+    Ok((contents[file_idx].seqnum, file_offset))
 }
 
 #[test]
 fn test_file_offset() {
-    use std::os::unix::prelude::AsRawFd;
+    use crate::storage::MemFile;
+    use std::sync::Arc;
 
-    assert_eq!(file_offset(&mut [], 0).err(), Some(Error::InvalidIndex));
+    assert_eq!(file_offset(&[], 0).err(), Some(Error::InvalidIndex));
 
-    let mock_file = || {
-        let f = File::open("/dev/zero").unwrap();
-        let fd = f.as_raw_fd();
-        (f, fd)
-    };
-    let (f1, f1_fd) = mock_file();
-    let one_file = &mut vec![ResourceFile {
+    let mock_file = || Box::new(MemFile(Arc::new(vec![])));
+    let one_file = &[ResourceFile {
         seqnum: 1,
         len: 100,
         offset: 0,
-        file: f1,
+        file: mock_file(),
+        map: None,
     }];
 
     let result = file_offset(one_file, 101);
@@ -381,27 +372,27 @@ fn test_file_offset() {
     assert_eq!(result.err(), Some(Error::InvalidIndex));
 
     let result = file_offset(one_file, 0);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f1_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(1));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(0));
 
     let result = file_offset(one_file, 99);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f1_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(1));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(99));
 
-    let (f1, f1_fd) = mock_file();
-    let (f2, f2_fd) = mock_file();
-    let two_files = &mut vec![
+    let two_files = &[
         ResourceFile {
             seqnum: 1,
             len: 100,
             offset: 0,
-            file: f1,
+            file: mock_file(),
+            map: None,
         },
         ResourceFile {
             seqnum: 2,
             len: 200,
             offset: 100,
-            file: f2,
+            file: mock_file(),
+            map: None,
         },
     ];
 
@@ -412,42 +403,42 @@ fn test_file_offset() {
     assert_eq!(result.err(), Some(Error::InvalidIndex));
 
     let result = file_offset(two_files, 0);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f1_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(1));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(0));
 
     let result = file_offset(two_files, 99);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f1_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(1));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(99));
 
     let result = file_offset(two_files, 100);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f2_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(2));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(0));
 
     let result = file_offset(two_files, 299);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f2_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(2));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(199));
 
-    let (f1, f1_fd) = mock_file();
-    let (f2, f2_fd) = mock_file();
-    let (f3, f3_fd) = mock_file();
-    let three_files = &mut vec![
+    let three_files = &[
         ResourceFile {
             seqnum: 1,
             len: 100,
             offset: 0,
-            file: f1,
+            file: mock_file(),
+            map: None,
         },
         ResourceFile {
             seqnum: 2,
             len: 200,
             offset: 100,
-            file: f2,
+            file: mock_file(),
+            map: None,
         },
         ResourceFile {
             seqnum: 3,
             len: 100,
             offset: 300,
-            file: f3,
+            file: mock_file(),
+            map: None,
         },
     ];
 
@@ -458,27 +449,27 @@ fn test_file_offset() {
     assert_eq!(result.err(), Some(Error::InvalidIndex));
 
     let result = file_offset(three_files, 0);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f1_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(1));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(0));
 
     let result = file_offset(three_files, 99);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f1_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(1));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(99));
 
     let result = file_offset(three_files, 100);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f2_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(2));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(0));
 
     let result = file_offset(three_files, 299);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f2_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(2));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(199));
 
     let result = file_offset(three_files, 300);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f3_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(3));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(0));
 
     let result = file_offset(three_files, 399);
-    assert_eq!(result.as_ref().map(|f| f.0.as_raw_fd()), Ok(f3_fd));
+    assert_eq!(result.as_ref().map(|f| f.0), Ok(3));
     assert_eq!(result.as_ref().map(|f| f.1), Ok(99));
 }
 
@@ -0,0 +1,220 @@
+use core::mem::size_of;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Error,
+    abi_utils::{LE32, TransmuteSafe},
+    resource::{
+        compress,
+        rsc::{IdxRecord, MapRecord},
+    },
+};
+
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+const DEFAULT_FILE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Writes `{rsc_name}-NNNN.rsc` resource files together with their `.idx` and `.map`
+/// index files, in the layout `Rsc` reads.
+///
+/// Items are packed into zlib blocks of about `block_size` uncompressed bytes.
+/// A new resource file is started before a block would make the current one larger
+/// than `file_size`; blocks are never split across files.
+#[derive(Debug)]
+pub struct RscWriter {
+    dir: PathBuf,
+    rsc_name: String,
+    block_size: usize,
+    file_size: usize,
+    // uncompressed items of the block that is being filled
+    block: Vec<u8>,
+    // first map record pointing into `block`
+    block_start: usize,
+    idx: Vec<IdxRecord>,
+    map: Vec<MapRecord>,
+    file: Option<BufWriter<File>>,
+    seqnum: u32,
+    file_len: usize,
+    // global offset of the next block, counted across all resource files
+    zoffset: usize,
+}
+
+impl RscWriter {
+    pub fn new(dir: &Path, rsc_name: &str) -> Self {
+        RscWriter {
+            dir: dir.to_path_buf(),
+            rsc_name: rsc_name.to_string(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            file_size: DEFAULT_FILE_SIZE,
+            block: Vec::new(),
+            block_start: 0,
+            idx: Vec::new(),
+            map: Vec::new(),
+            file: None,
+            seqnum: 0,
+            file_len: 0,
+            zoffset: 0,
+        }
+    }
+
+    /// Uncompressed size a block may grow to. An item larger than that gets a block of its own.
+    pub fn set_block_size(&mut self, bytes: usize) {
+        self.block_size = bytes;
+    }
+
+    /// Size a resource file may grow to. A file always holds at least one block.
+    pub fn set_file_size(&mut self, bytes: usize) {
+        self.file_size = bytes;
+    }
+
+    /// Adds an item. Ids have to be strictly ascending.
+    pub fn add(&mut self, id: u32, item: &[u8]) -> Result<(), Error> {
+        if self
+            .idx
+            .last()
+            .is_some_and(|last| last.item_id.read() >= id)
+        {
+            return Err(Error::InvalidArg);
+        }
+        let len = u32::try_from(item.len()).map_err(|_| Error::RecordTooLarge)?;
+        let item_size = size_of::<LE32>() + item.len();
+        if !self.block.is_empty() && self.block.len() + item_size > self.block_size {
+            self.flush_block()?;
+        }
+        let ioffset = self.block.len() as u32;
+        self.block.extend_from_slice(&len.to_le_bytes());
+        self.block.extend_from_slice(item);
+
+        self.idx.push(IdxRecord {
+            item_id: id.into(),
+            map_idx: (self.map.len() as u32).into(),
+        });
+        self.map.push(MapRecord {
+            // filled in once the block is written
+            zoffset: 0.into(),
+            ioffset: ioffset.into(),
+        });
+        Ok(())
+    }
+
+    /// Writes the last block and the index files.
+    pub fn finish(mut self) -> Result<(), Error> {
+        if !self.block.is_empty() {
+            self.flush_block()?;
+        }
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+
+        let stem = self.dir.join(&self.rsc_name);
+        let len = self.idx.len() as u32;
+        let mut idx_file = BufWriter::new(File::create(stem.with_extension("idx"))?);
+        idx_file.write_all(&len.to_le_bytes())?;
+        idx_file.write_all(&[0; 4])?;
+        idx_file.write_all(IdxRecord::slice_as_bytes(&self.idx))?;
+        idx_file.flush()?;
+
+        let mut map_file = BufWriter::new(File::create(stem.with_extension("map"))?);
+        map_file.write_all(&[0; 4])?;
+        map_file.write_all(&len.to_le_bytes())?;
+        map_file.write_all(MapRecord::slice_as_bytes(&self.map))?;
+        map_file.flush()?;
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> Result<(), Error> {
+        let zblock = compress(&self.block);
+        let zlen = u32::try_from(zblock.len()).map_err(|_| Error::RecordTooLarge)?;
+        let block_size = size_of::<LE32>() + zblock.len();
+        if self.file.is_none() || (self.file_len > 0 && self.file_len + block_size > self.file_size)
+        {
+            self.next_file()?;
+        }
+        let file = self.file.as_mut().expect("a resource file was just opened");
+        file.write_all(&zlen.to_le_bytes())?;
+        file.write_all(&zblock)?;
+
+        let zoffset = u32::try_from(self.zoffset).map_err(|_| Error::RecordTooLarge)?;
+        for rec in &mut self.map[self.block_start..] {
+            rec.zoffset = zoffset.into();
+        }
+        self.block_start = self.map.len();
+        self.block.clear();
+        self.file_len += block_size;
+        self.zoffset += block_size;
+        Ok(())
+    }
+
+    fn next_file(&mut self) -> Result<(), Error> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        self.seqnum += 1;
+        let name = format!("{}-{:04}.rsc", self.rsc_name, self.seqnum);
+        self.file = Some(BufWriter::new(File::create(self.dir.join(name))?));
+        self.file_len = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DirStorage, LoadMode, fixture::temp_dir, resource::Rsc};
+
+    fn item(id: u32) -> Vec<u8> {
+        format!("<item id=\"{id}\">{}</item>", "x".repeat(id as usize % 50)).into_bytes()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_dir("rsc_writer");
+        let ids: Vec<u32> = (1..300).chain([1000, 5000]).collect();
+        let mut writer = RscWriter::new(&dir, "contents");
+        writer.set_block_size(512);
+        writer.set_file_size(1024);
+        for id in &ids {
+            writer.add(*id, &item(*id)).unwrap();
+        }
+        writer.finish().unwrap();
+        assert!(dir.join("contents-0002.rsc").exists());
+
+        for mode in [LoadMode::Mmap, LoadMode::Read] {
+            let rsc = Rsc::new(&DirStorage::with_mode(&dir, mode), "", "contents").unwrap();
+            assert_eq!(rsc.len(), ids.len());
+            for (i, id) in ids.iter().enumerate() {
+                assert_eq!(*rsc.get(*id).unwrap(), item(*id));
+                let (by_idx, _) = rsc.get_by_idx(i).unwrap();
+                assert_eq!(by_idx, *id);
+            }
+            assert_eq!(rsc.get(999), Err(Error::NotFound));
+        }
+    }
+
+    #[test]
+    fn large_item() {
+        let dir = temp_dir("rsc_writer_large");
+        let mut writer = RscWriter::new(&dir, "contents");
+        writer.set_block_size(16);
+        writer.add(0, b"small").unwrap();
+        writer.add(1, &[7; 100]).unwrap();
+        writer.add(2, b"").unwrap();
+        writer.finish().unwrap();
+
+        let rsc = Rsc::new(&DirStorage::with_mode(&dir, LoadMode::Read), "", "contents").unwrap();
+        assert_eq!(*rsc.get(0).unwrap(), *b"small");
+        assert_eq!(*rsc.get(1).unwrap(), [7; 100]);
+        assert!(rsc.get(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ascending_ids() {
+        let mut writer = RscWriter::new(Path::new("."), "contents");
+        writer.add(5, b"a").unwrap();
+        assert_eq!(writer.add(5, b"b"), Err(Error::InvalidArg));
+        assert_eq!(writer.add(4, b"b"), Err(Error::InvalidArg));
+    }
+}
@@ -0,0 +1,212 @@
+//! Where the files of a dictionary are read from: a directory, buffers in
+//! memory or a zip archive of the dictionary directory.
+
+mod archive;
+mod dir;
+mod mem;
+
+use std::{path::Path, sync::Arc};
+
+pub use archive::ZipStorage;
+pub use dir::DirStorage;
+pub use mem::MemStorage;
+
+pub(crate) use mem::MemFile;
+
+use crate::{Error, LoadMode};
+
+/// The contents of a file that is in memory already, or memory mapped.
+pub type Bytes = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// The files of a dictionary. Paths are relative to the dictionary and
+/// separated by `/`, like `contents/contents.map`.
+pub trait Storage: Send + Sync {
+    /// The names of the files in the directory `dir`, in no particular order.
+    /// `""` is the dictionary itself.
+    fn list(&self, dir: &str) -> Result<Vec<String>, Error>;
+
+    /// Opens the file at `path` for reading.
+    fn open(&self, path: &str) -> Result<Box<dyn ReadAt>, Error>;
+
+    fn exists(&self, path: &str) -> bool {
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+        self.list(dir)
+            .is_ok_and(|names| names.iter().any(|n| n == name))
+    }
+}
+
+/// An open file of a `Storage`, read at any offset without a cursor.
+pub trait ReadAt: Send + Sync {
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills `buf` with the bytes at `offset`, all of them or an error.
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error>;
+
+    /// All of the file, if it can be borrowed instead of read.
+    fn bytes(&self) -> Option<Bytes> {
+        None
+    }
+}
+
+/// `name` in the directory `dir` of a `Storage`.
+pub(crate) fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// The names of the files of `paths` that are directly in `dir`.
+fn names_in<'a>(paths: impl Iterator<Item = &'a String>, dir: &str) -> Vec<String> {
+    paths
+        .filter_map(|path| match path.rsplit_once('/') {
+            Some((parent, name)) if parent == dir => Some(name.to_string()),
+            None if dir.is_empty() => Some(path.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The directory `dir` of another storage.
+pub(crate) struct SubDir {
+    storage: Arc<dyn Storage>,
+    dir: String,
+}
+
+impl SubDir {
+    pub(crate) fn new(storage: Arc<dyn Storage>, dir: &str) -> Self {
+        SubDir {
+            storage,
+            dir: dir.to_string(),
+        }
+    }
+}
+
+impl Storage for SubDir {
+    fn list(&self, dir: &str) -> Result<Vec<String>, Error> {
+        self.storage
+            .list(join(&self.dir, dir).trim_end_matches('/'))
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn ReadAt>, Error> {
+        self.storage.open(&join(&self.dir, path))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.storage.exists(&join(&self.dir, path))
+    }
+}
+
+/// The files at `path`: a zip archive if it is a file, otherwise a directory.
+pub(crate) fn open_path(path: &Path, mode: LoadMode) -> Result<Arc<dyn Storage>, Error> {
+    if path.is_file() {
+        Ok(Arc::new(ZipStorage::open_with_mode(path, mode)?))
+    } else {
+        Ok(Arc::new(DirStorage::with_mode(path, mode)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Cursor, Write},
+    };
+
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+    use crate::{
+        MonokakidoDict, PageItemId,
+        fixture::{SAKURA_SOUND, dict_files, sound_data, temp_dir},
+    };
+
+    /// The fixture dictionary as a zip archive, resources stored and the rest deflated.
+    fn zip_dict(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            let method = if name.ends_with("rsc") {
+                CompressionMethod::Stored
+            } else {
+                CompressionMethod::Deflated
+            };
+            zip.start_file(
+                name,
+                SimpleFileOptions::default().compression_method(method),
+            )
+            .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn assert_dict(dict: &MonokakidoDict) {
+        let items = dict.look_up("サクラ", None).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].0.0, "00001");
+        assert_eq!(dict.look_up("ハナミ", None).unwrap().len(), 1);
+        assert_eq!(dict.look_up("イッポン", None).unwrap().len(), 1);
+        assert!(
+            dict.pages
+                .get_page(PageItemId { page: 2, item: 0 })
+                .unwrap()
+                .contains("あめ")
+        );
+        assert_eq!(
+            dict.audio.get(SAKURA_SOUND).unwrap().as_ref(),
+            sound_data(SAKURA_SOUND)
+        );
+        assert_eq!(dict.audio.idx_iter().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn mem_storage() {
+        let mut storage = MemStorage::new();
+        for (name, data) in dict_files("storage_mem") {
+            storage.insert(&name, data);
+        }
+        let mut names = storage.list("key").unwrap();
+        names.sort();
+        assert_eq!(
+            names,
+            ["compound.keyindex", "headword.keyindex", "numeral.keyindex"]
+        );
+        assert!(storage.exists("audio/index.nidx"));
+        assert!(!storage.exists("audio/index"));
+        assert!(storage.open("audio").is_err());
+
+        let dict = MonokakidoDict::open_with_storage(Arc::new(storage)).unwrap();
+        assert_dict(&dict);
+    }
+
+    #[test]
+    fn zip_storage() {
+        let bundle = zip_dict(&dict_files("storage_zip"));
+
+        let storage = ZipStorage::from_bytes(bundle.clone()).unwrap();
+        let mut names = storage.list("contents").unwrap();
+        names.sort();
+        assert_eq!(names, ["contents-0001.rsc", "contents.idx", "contents.map"]);
+        let file = storage.open("contents/contents-0001.rsc").unwrap();
+        let mut buf = [0; 4];
+        file.read_exact_at(&mut buf, 0).unwrap();
+        assert!(file.read_exact_at(&mut buf, file.len() - 2).is_err());
+        assert!(file.bytes().is_some());
+        let dict = MonokakidoDict::open_with_storage(Arc::new(storage)).unwrap();
+        assert_dict(&dict);
+
+        // a path to a file is opened as a zip archive
+        let dir = temp_dir("storage_bundle");
+        let path = dir.join("nhk.zip");
+        fs::write(&path, bundle).unwrap();
+        for mode in [LoadMode::Mmap, LoadMode::Read] {
+            let dict = MonokakidoDict::open_with_mode(path.to_str().unwrap(), mode).unwrap();
+            assert_dict(&dict);
+        }
+    }
+}
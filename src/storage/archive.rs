@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use zip::{CompressionMethod, ZipArchive};
+
+use super::{Bytes, MemFile, ReadAt, Storage, dir::DirFile, names_in};
+use crate::{Error, LoadMode};
+
+/// A zip archive of a dictionary directory, with `contents/`, `key/` and so on
+/// at its root. Stored files are read in place, compressed ones are inflated
+/// into memory when they are opened.
+pub struct ZipStorage {
+    archive: Arc<dyn ReadAt>,
+    entries: HashMap<String, Entry>,
+    zip: Mutex<ZipArchive<Cursor>>,
+}
+
+struct Entry {
+    index: usize,
+    /// Where the data is in the archive, for stored files.
+    stored: Option<Range<u64>>,
+}
+
+impl ZipStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open_with_mode(path, LoadMode::default())
+    }
+
+    pub fn open_with_mode(path: impl AsRef<Path>, mode: LoadMode) -> Result<Self, Error> {
+        Self::new(Box::new(DirFile::open(path.as_ref(), mode)?))
+    }
+
+    pub fn from_bytes(archive: Vec<u8>) -> Result<Self, Error> {
+        Self::new(Box::new(MemFile(Arc::new(archive))))
+    }
+
+    pub fn new(archive: Box<dyn ReadAt>) -> Result<Self, Error> {
+        let archive: Arc<dyn ReadAt> = archive.into();
+        let mut zip = ZipArchive::new(Cursor {
+            file: archive.clone(),
+            pos: 0,
+        })?;
+        let mut entries = HashMap::new();
+        for index in 0..zip.len() {
+            let file = zip.by_index_raw(index)?;
+            if file.is_dir() {
+                continue;
+            }
+            let stored = (file.compression() == CompressionMethod::Stored)
+                .then(|| file.data_start()..file.data_start() + file.size());
+            entries.insert(file.name().to_string(), Entry { index, stored });
+        }
+        Ok(ZipStorage {
+            archive,
+            entries,
+            zip: Mutex::new(zip),
+        })
+    }
+}
+
+impl Storage for ZipStorage {
+    fn list(&self, dir: &str) -> Result<Vec<String>, Error> {
+        Ok(names_in(self.entries.keys(), dir))
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn ReadAt>, Error> {
+        let entry = self.entries.get(path).ok_or(Error::FopenError)?;
+        if let Some(range) = &entry.stored {
+            return Ok(Box::new(Slice {
+                archive: self.archive.clone(),
+                range: range.clone(),
+            }));
+        }
+        // the archive stays consistent even if a reader panicked
+        let mut zip = self.zip.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut file = zip.by_index(entry.index)?;
+        let mut contents = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut contents)?;
+        Ok(Box::new(MemFile(Arc::new(contents))))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+}
+
+/// A stored file, a range of the archive.
+struct Slice {
+    archive: Arc<dyn ReadAt>,
+    range: Range<u64>,
+}
+
+impl ReadAt for Slice {
+    fn len(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        if offset.saturating_add(buf.len() as u64) > self.len() {
+            return Err(Error::IOError);
+        }
+        self.archive.read_exact_at(buf, self.range.start + offset)
+    }
+
+    fn bytes(&self) -> Option<Bytes> {
+        let bytes = self.archive.bytes()?;
+        let range = self.range.start as usize..self.range.end as usize;
+        // a damaged archive is read instead, reads report the error
+        (*bytes).as_ref().get(range.clone())?;
+        Some(Arc::new(SubBytes { bytes, range }))
+    }
+}
+
+struct SubBytes {
+    bytes: Bytes,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for SubBytes {
+    fn as_ref(&self) -> &[u8] {
+        &(*self.bytes).as_ref()[self.range.clone()]
+    }
+}
+
+/// `Read` and `Seek` over a `ReadAt`, for `ZipArchive`.
+struct Cursor {
+    file: Arc<dyn ReadAt>,
+    pos: u64,
+}
+
+impl Read for Cursor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = u64::min(buf.len() as u64, self.file.len().saturating_sub(self.pos)) as usize;
+        self.file
+            .read_exact_at(&mut buf[..n], self.pos)
+            .map_err(|e| io::Error::other(format!("{e:?}")))?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Cursor {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => self.file.len().checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or(io::ErrorKind::InvalidInput)?;
+        Ok(self.pos)
+    }
+}
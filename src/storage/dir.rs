@@ -0,0 +1,125 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+
+use super::{Bytes, ReadAt, Storage};
+use crate::{Error, LoadMode, abi_utils::map_file};
+
+/// A dictionary directory, like `assets/` of the add-on.
+#[derive(Debug, Clone)]
+pub struct DirStorage {
+    path: PathBuf,
+    mode: LoadMode,
+}
+
+impl DirStorage {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self::with_mode(path, LoadMode::default())
+    }
+
+    pub fn with_mode(path: impl AsRef<Path>, mode: LoadMode) -> Self {
+        DirStorage {
+            path: path.as_ref().to_path_buf(),
+            mode,
+        }
+    }
+}
+
+impl Storage for DirStorage {
+    fn list(&self, dir: &str) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(self.path.join(dir))? {
+            if let Ok(name) = entry?.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn ReadAt>, Error> {
+        Ok(Box::new(DirFile::open(&self.path.join(path), self.mode)?))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.path.join(path).exists()
+    }
+}
+
+pub(super) struct DirFile {
+    #[cfg(any(unix, windows))]
+    file: File,
+    // without positional reads the cursor is shared, reads seek under the lock
+    #[cfg(not(any(unix, windows)))]
+    file: std::sync::Mutex<File>,
+    len: u64,
+    map: Option<Arc<Mmap>>,
+}
+
+impl DirFile {
+    pub(super) fn open(path: &Path, mode: LoadMode) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        Ok(DirFile {
+            len: file.metadata()?.len(),
+            map: map_file(&file, mode).map(Arc::new),
+            #[cfg(any(unix, windows))]
+            file,
+            #[cfg(not(any(unix, windows)))]
+            file: std::sync::Mutex::new(file),
+        })
+    }
+}
+
+impl ReadAt for DirFile {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Reads at `offset` without moving a shared cursor where the platform has
+    /// positional reads, so `&File` is enough.
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileExt;
+            self.file
+                .read_exact_at(buf, offset)
+                .map_err(|_| Error::IOError)
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::FileExt;
+            let (mut buf, mut offset) = (buf, offset);
+            while !buf.is_empty() {
+                match self.file.seek_read(buf, offset) {
+                    Ok(0) => return Err(Error::IOError),
+                    Ok(n) => {
+                        buf = &mut buf[n..];
+                        offset += n as u64;
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(_) => return Err(Error::IOError),
+                }
+            }
+            Ok(())
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            use std::io::{Read, Seek, SeekFrom};
+            // a reader that panicked left no state behind, the next read seeks anyway
+            let mut file = self
+                .file
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(buf)?;
+            Ok(())
+        }
+    }
+
+    fn bytes(&self) -> Option<Bytes> {
+        self.map.clone().map(|map| map as Bytes)
+    }
+}
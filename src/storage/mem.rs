@@ -0,0 +1,59 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{Bytes, ReadAt, Storage, names_in};
+use crate::Error;
+
+/// Dictionary files held in memory, by their path in the dictionary.
+#[derive(Debug, Clone, Default)]
+pub struct MemStorage {
+    files: HashMap<String, Arc<Vec<u8>>>,
+}
+
+impl MemStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the file at `path`, like `key/headword.keyindex`, replacing an earlier one.
+    pub fn insert(&mut self, path: &str, contents: Vec<u8>) {
+        self.files.insert(path.to_string(), Arc::new(contents));
+    }
+}
+
+impl Storage for MemStorage {
+    fn list(&self, dir: &str) -> Result<Vec<String>, Error> {
+        Ok(names_in(self.files.keys(), dir))
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn ReadAt>, Error> {
+        let contents = self.files.get(path).ok_or(Error::FopenError)?;
+        Ok(Box::new(MemFile(contents.clone())))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+}
+
+pub(crate) struct MemFile(pub(crate) Arc<Vec<u8>>);
+
+impl ReadAt for MemFile {
+    fn len(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        let start = usize::try_from(offset).map_err(|_| Error::IOError)?;
+        let bytes = self
+            .0
+            .get(start..)
+            .and_then(|tail| tail.get(..buf.len()))
+            .ok_or(Error::IOError)?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn bytes(&self) -> Option<Bytes> {
+        Some(self.0.clone())
+    }
+}
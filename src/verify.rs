@@ -0,0 +1,376 @@
+use std::{collections::HashSet, fmt, path::Path, sync::Arc};
+
+use crate::{
+    Audio, Error, Keys, LoadMode, Pages,
+    info::detect,
+    pages::audio_hrefs,
+    storage::{Storage, open_path},
+};
+
+/// Result of `verify`. Every broken record is listed, checking goes on after the first one.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub pages: usize,
+    pub keys: usize,
+    pub audio: usize,
+    pub problems: Vec<Problem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A part of the dictionary can't be opened, so it was not checked.
+    Open { part: String, error: Error },
+    /// The page at this position of the contents index can't be read.
+    Page { idx: usize, error: Error },
+    /// The word at this position of a key index can't be read.
+    Key {
+        key_index: String,
+        idx: usize,
+        error: Error,
+    },
+    /// A key refers to a page that is not in the contents.
+    MissingPage {
+        key_index: String,
+        word: String,
+        page: u32,
+    },
+    /// The audio clip at this position of the audio index can't be read.
+    Audio { idx: usize, error: Error },
+    /// A page links to an audio clip that is not in the audio index.
+    MissingAudio { page: u32, href: String },
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Open { part, error } => write!(f, "{part}: can't open ({error:?})"),
+            Problem::Page { idx, error } => write!(f, "contents: page #{idx} ({error:?})"),
+            Problem::Key {
+                key_index,
+                idx,
+                error,
+            } => write!(f, "{key_index}: key #{idx} ({error:?})"),
+            Problem::MissingPage {
+                key_index,
+                word,
+                page,
+            } => write!(f, "{key_index}: {word} refers to missing page {page}"),
+            Problem::Audio { idx, error } => write!(f, "audio: clip #{idx} ({error:?})"),
+            Problem::MissingAudio { page, href } => {
+                write!(f, "contents: page {page} links to missing audio {href}")
+            }
+        }
+    }
+}
+
+/// Reads every page, key and audio clip of the dictionary at `path` and checks
+/// that keys and audio links point at something that exists. Parts the
+/// dictionary doesn't have are not reported, unless the pages need them: audio
+/// that is missing while pages link to clips is.
+pub fn verify(path: &str) -> Report {
+    let mut report = Report::default();
+    let (info, storage) = match open_path(Path::new(path), LoadMode::default()).and_then(detect) {
+        Ok(dict) => dict,
+        Err(error) => {
+            report.problems.push(Problem::Open {
+                part: "dictionary".to_string(),
+                error,
+            });
+            return report;
+        }
+    };
+
+    let pages = verify_pages(storage.clone(), &mut report);
+    let has_hrefs = pages.as_ref().is_some_and(|pages| !pages.hrefs.is_empty());
+    let audio_ids = if info.audio || has_hrefs {
+        verify_audio(storage.clone(), &mut report)
+    } else {
+        None
+    };
+    if let (Some(pages), Some(audio_ids)) = (&pages, &audio_ids) {
+        for (page, href) in &pages.hrefs {
+            let id = href.strip_suffix(".aac").unwrap_or(href);
+            if !audio_ids.contains(id) {
+                report.problems.push(Problem::MissingAudio {
+                    page: *page,
+                    href: href.clone(),
+                });
+            }
+        }
+    }
+
+    for key_index in &info.key_indexes {
+        let page_ids = pages.as_ref().map(|pages| &pages.ids);
+        verify_keys(&*storage, key_index, page_ids, &mut report);
+    }
+    report
+}
+
+/// Ids of all readable pages and the audio links found in them.
+struct PageLinks {
+    ids: HashSet<u32>,
+    // page id, href
+    hrefs: Vec<(u32, String)>,
+}
+
+fn verify_pages(storage: Arc<dyn Storage>, report: &mut Report) -> Option<PageLinks> {
+    let pages = Pages::with_storage(storage).and_then(|pages| pages.init().map(|_| pages));
+    let pages = match pages {
+        Ok(pages) => pages,
+        Err(error) => {
+            report.problems.push(Problem::Open {
+                part: "contents".to_string(),
+                error,
+            });
+            return None;
+        }
+    };
+
+    let mut ids = HashSet::new();
+    let mut hrefs = Vec::new();
+    for idx in pages.idx_iter().unwrap_or_default() {
+        report.pages += 1;
+        let (id, xml) = match pages.page_by_idx(idx) {
+            Ok(page) => page,
+            Err(error) => {
+                report.problems.push(Problem::Page { idx, error });
+                continue;
+            }
+        };
+        ids.insert(id);
+        for href in audio_hrefs(&xml) {
+            match href {
+                Ok(href) => hrefs.push((id, href.to_string())),
+                Err(error) => report.problems.push(Problem::Page { idx, error }),
+            }
+        }
+    }
+    Some(PageLinks { ids, hrefs })
+}
+
+/// Ids of all readable audio clips.
+fn verify_audio(storage: Arc<dyn Storage>, report: &mut Report) -> Option<HashSet<String>> {
+    let audio = Audio::with_storage(storage).and_then(|audio| audio.init().map(|_| audio));
+    let audio = match audio {
+        Ok(audio) => audio,
+        Err(error) => {
+            report.problems.push(Problem::Open {
+                part: "audio".to_string(),
+                error,
+            });
+            return None;
+        }
+    };
+
+    let mut ids = HashSet::new();
+    for idx in audio.idx_iter().unwrap_or_default() {
+        report.audio += 1;
+        match audio.get_by_idx(idx) {
+            Ok((id, _)) => {
+                ids.insert(id.to_string());
+            }
+            Err(error) => report.problems.push(Problem::Audio { idx, error }),
+        }
+    }
+    Some(ids)
+}
+
+fn verify_keys(
+    storage: &dyn Storage,
+    key_index: &str,
+    page_ids: Option<&HashSet<u32>>,
+    report: &mut Report,
+) {
+    let keys = match Keys::with_storage(storage, key_index) {
+        Ok(keys) => keys,
+        Err(error) => {
+            report.problems.push(Problem::Open {
+                part: key_index.to_string(),
+                error,
+            });
+            return;
+        }
+    };
+
+    for idx in 0..keys.index_prefix.len() {
+        report.keys += 1;
+        let (word, pages) = match keys.get_idx(&keys.index_prefix, idx) {
+            Ok(key) => key,
+            Err(error) => {
+                report.problems.push(Problem::Key {
+                    key_index: key_index.to_string(),
+                    idx,
+                    error,
+                });
+                continue;
+            }
+        };
+        let Some(page_ids) = page_ids else { continue };
+        for id in pages {
+            if !page_ids.contains(&id.page) {
+                report.problems.push(Problem::MissingPage {
+                    key_index: key_index.to_string(),
+                    word: word.to_string(),
+                    page: id.page,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        KeysWriter, NrscFormat, NrscWriter, PageItemId,
+        dict::{COMPOUND_KEYS, HEADWORD_KEYS, NUMERAL_KEYS},
+        fixture::{AME_SOUND, IPPON_SOUND, SAKURA_SOUND, build_dict},
+    };
+
+    #[test]
+    fn complete_dict() {
+        let dir = build_dict("verify_ok");
+        let report = verify(dir.to_str().unwrap());
+        assert_eq!(report.problems, []);
+        assert!(report.is_ok());
+        assert_eq!(report.pages, 4);
+        assert_eq!(report.keys, 4);
+        assert_eq!(report.audio, 3);
+    }
+
+    #[test]
+    fn dangling_references() {
+        let dir = build_dict("verify_dangling");
+        let path = dir.to_str().unwrap();
+
+        let audio = dir.join("audio");
+        fs::remove_dir_all(&audio).unwrap();
+        fs::create_dir(&audio).unwrap();
+        let mut writer = NrscWriter::new(&audio);
+        writer
+            .add(AME_SOUND, b"", NrscFormat::Uncompressed)
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut keys = KeysWriter::new();
+        keys.add("ユキ", [PageItemId { page: 99, item: 0 }]);
+        keys.write(path, HEADWORD_KEYS).unwrap();
+
+        let report = verify(path);
+        assert_eq!(
+            report.problems,
+            [
+                Problem::MissingAudio {
+                    page: 1,
+                    href: format!("{SAKURA_SOUND}.aac"),
+                },
+                Problem::MissingAudio {
+                    page: 3,
+                    href: format!("{IPPON_SOUND}.aac"),
+                },
+                Problem::MissingPage {
+                    key_index: HEADWORD_KEYS.to_string(),
+                    word: "ユキ".to_string(),
+                    page: 99,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_key_index() {
+        let dir = build_dict("verify_truncated_keys");
+        let path = dir.to_str().unwrap();
+        let mut keys = KeysWriter::new();
+        keys.add(
+            "ア",
+            [
+                PageItemId { page: 1, item: 0 },
+                PageItemId { page: 2, item: 0 },
+            ],
+        );
+        keys.add("イ", [PageItemId { page: 2, item: 0 }]);
+        keys.add("ウ", [PageItemId { page: 4, item: 0 }]);
+        keys.write(path, HEADWORD_KEYS).unwrap();
+
+        // cut the word table in the middle of the page list of ア, before those
+        // of イ and ウ, and move the indexes after it
+        const WORDS_OFFSET: usize = 32;
+        const WORDS_LEN: usize = 32;
+        let file = dir.join("key").join(HEADWORD_KEYS);
+        let data = fs::read(&file).unwrap();
+        let idx_offset = u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+        let mut truncated = data[..WORDS_OFFSET + WORDS_LEN].to_vec();
+        truncated[12..16].copy_from_slice(&((WORDS_OFFSET + WORDS_LEN) as u32).to_le_bytes());
+        truncated.extend_from_slice(&data[idx_offset..]);
+        fs::write(&file, truncated).unwrap();
+
+        let report = verify(path);
+        // and the compound and numeral keys of the fixture
+        assert_eq!(report.keys, 5);
+        let problems: Vec<_> = (0..3)
+            .map(|idx| Problem::Key {
+                key_index: HEADWORD_KEYS.to_string(),
+                idx,
+                error: Error::InvalidIndex,
+            })
+            .collect();
+        assert_eq!(report.problems, problems);
+    }
+
+    #[test]
+    fn missing_and_broken_files() {
+        let dir = build_dict("verify_broken");
+        let path = dir.to_str().unwrap();
+        fs::remove_dir_all(dir.join("audio")).unwrap();
+        fs::remove_file(dir.join("key").join(NUMERAL_KEYS)).unwrap();
+        fs::write(dir.join("key").join(COMPOUND_KEYS), b"broken").unwrap();
+        let rsc = dir.join("contents").join("contents-0001.rsc");
+        let len = fs::metadata(&rsc).unwrap().len();
+        fs::File::options()
+            .write(true)
+            .open(&rsc)
+            .unwrap()
+            .set_len(len - 10)
+            .unwrap();
+
+        let report = verify(path);
+        assert_eq!(report.pages, 4);
+        assert_eq!(report.audio, 0);
+        assert_eq!(report.keys, 2);
+        assert!(
+            report
+                .problems
+                .iter()
+                .any(|p| matches!(p, Problem::Page { .. }))
+        );
+        // the pages link to audio the dictionary doesn't have, missing numeral
+        // keys are not a problem
+        let opened: Vec<_> = report
+            .problems
+            .iter()
+            .filter(|p| matches!(p, Problem::Open { .. }))
+            .collect();
+        assert_eq!(
+            opened,
+            [
+                &Problem::Open {
+                    part: "audio".to_string(),
+                    error: Error::IOError,
+                },
+                &Problem::Open {
+                    part: COMPOUND_KEYS.to_string(),
+                    error: Error::IOError,
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{Cursor, ErrorKind};
+
+use svg::{Document, node::element::Rectangle};
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as DecodeError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+use crate::Error;
+
+// same height as the accent diagram from `gen_svg`, so both can be placed side by side
+const HEIGHT: usize = 90;
+const CENTER_Y: f32 = 45.0;
+const MAX_AMPLITUDE: f32 = 40.0;
+const BAR_WIDTH: usize = 3;
+const BAR_GAP: usize = 1;
+// one bar per 20 ms of audio
+const BARS_PER_SECOND: usize = 50;
+const MAX_BARS: usize = 250;
+const BAR_STYLE: &str = "fill:#fff;stroke:#000;stroke-width:1;";
+
+pub fn gen_waveform_svg(aac: &[u8]) -> Result<String, Error> {
+    let (samples, sample_rate) = decode_aac(aac)?;
+    let window = (sample_rate as usize / BARS_PER_SECOND).max(1);
+    let peaks = peaks(&samples, window);
+    Ok(draw_waveform(&peaks))
+}
+
+/// Decodes an ADTS stream and mixes all channels down to mono.
+fn decode_aac(aac: &[u8]) -> Result<(Vec<f32>, u32), Error> {
+    let mss = MediaSourceStream::new(Box::new(Cursor::new(aac.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension("aac");
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|_| Error::AudioDecodeError)?;
+    let mut format = probed.format;
+    let track = format.default_track().ok_or(Error::AudioDecodeError)?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or(Error::AudioDecodeError)?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|_| Error::AudioDecodeError)?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(_) => return Err(Error::AudioDecodeError),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a single broken frame should not hide the rest of the clip
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(_) => return Err(Error::AudioDecodeError),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        samples.extend(
+            buf.samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+    if samples.is_empty() {
+        return Err(Error::AudioDecodeError);
+    }
+    Ok((samples, sample_rate))
+}
+
+/// Peak amplitude of every `window` samples, normalised to the loudest window.
+fn peaks(samples: &[f32], window: usize) -> Vec<f32> {
+    let mut peaks: Vec<f32> = samples
+        .chunks(window)
+        .map(|w| w.iter().fold(0.0_f32, |max, s| max.max(s.abs())))
+        .collect();
+    if peaks.len() > MAX_BARS {
+        let step = peaks.len().div_ceil(MAX_BARS);
+        peaks = peaks
+            .chunks(step)
+            .map(|c| c.iter().fold(0.0_f32, |max, p| max.max(*p)))
+            .collect();
+    }
+    let loudest = peaks.iter().fold(0.0_f32, |max, p| max.max(*p));
+    if loudest > 0.0 {
+        peaks.iter_mut().for_each(|p| *p /= loudest);
+    }
+    peaks
+}
+
+fn draw_waveform(peaks: &[f32]) -> String {
+    let svg_width = (peaks.len() * (BAR_WIDTH + BAR_GAP)).max(1);
+    let mut doc = Document::new()
+        .set("width", svg_width)
+        .set("height", HEIGHT)
+        .set("viewBox", (0, 0, svg_width, HEIGHT));
+
+    for (i, peak) in peaks.iter().enumerate() {
+        // keep silent parts visible as a thin line
+        let half = (peak * MAX_AMPLITUDE).max(0.5);
+        let bar = Rectangle::new()
+            .set("x", i * (BAR_WIDTH + BAR_GAP))
+            .set("y", CENTER_Y - half)
+            .set("width", BAR_WIDTH)
+            .set("height", half * 2.0)
+            .set("style", BAR_STYLE);
+        doc = doc.add(bar);
+    }
+    doc.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn peaks_normalised() {
+        let samples = [0.0, 0.25, -0.5, 0.1, 0.0, 0.0];
+        assert_eq!(peaks(&samples, 2), vec![0.5, 1.0, 0.0]);
+    }
+    #[test]
+    fn peaks_capped() {
+        let samples = vec![0.5; MAX_BARS * 3];
+        assert_eq!(peaks(&samples, 1).len(), MAX_BARS);
+    }
+    #[test]
+    fn svg_size() {
+        let svg = draw_waveform(&[0.0, 1.0, 0.5]);
+        assert!(svg.contains("width=\"12\""));
+        assert!(svg.contains("height=\"90\""));
+        assert_eq!(svg.matches("<rect").count(), 3);
+    }
+    #[test]
+    fn invalid_audio() {
+        assert_eq!(
+            gen_waveform_svg(b"not an aac stream"),
+            Err(Error::AudioDecodeError)
+        );
+    }
+}